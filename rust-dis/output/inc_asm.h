@@ -4,6 +4,10 @@
 #define STRINGIFY_(x) #x
 #define STRINGIFY(x) STRINGIFY_(x)
 
+// Functions that are equivalent but do not match yet keep their C behind
+// #ifndef NON_MATCHING with INCLUDE_ASM as the fallback. Build with
+// -DNON_MATCHING to use that C instead of the asm.
+
 #ifndef PERMUTER
 
 #ifndef INCLUDE_ASM
@@ -16,11 +20,20 @@
             ".include \"" FOLDER "/" #NAME ".s\"\n");
 #endif
 
+#ifndef INCLUDE_RODATA
+#define INCLUDE_RODATA(FOLDER, NAME)                                           \
+    __asm__(".section .rodata\n"                                               \
+            "\t.align\t2\n"                                                    \
+            ".include \"" FOLDER "/" #NAME ".s\"\n"                            \
+            ".text\n");
+#endif
+
 // omit .global
 __asm__(".include \"macro.inc\"\n");
 
 #else
-#define INCLUDE_ASM(FOLDER, NAME)
+#define INCLUDE_ASM(FOLDER, NAME, LABEL)
+#define INCLUDE_RODATA(FOLDER, NAME)
 #endif
 
 #endif
@@ -0,0 +1,1063 @@
+// The command line: every subcommand and the output formats they print.
+
+use crate::output::{FileChange, OutputPlan};
+use crate::progress::{
+    decompiled_copies, format_progress, format_shared_functions, progress_report, shared_copies,
+    ProgressFormat, SharedCopy,
+};
+use crate::{
+    check_build, diagnose, find_funcs, find_includes, handle_code_section, handle_segments,
+    load_config, matching, naming, objects, process_segments, read_binary, read_user_prototypes,
+    read_user_symbols, segment_bss, segment_end, sh2_disasm, signatures, trace, Config, Error,
+    FunctionRange, Options, ProcessedSection, Subsegment, WithPath,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::Path;
+
+#[derive(Parser)]
+#[command(name = "rust-dis", about = "Sega Saturn binary splitting tool")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print progress details, repeat for a trace of every decision
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Write every warning to this file as sorted JSON lines
+    #[arg(long, global = true)]
+    pub diagnostics: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Split the target described by a config into asm, C, symbols and a linker script
+    Split {
+        /// YAML config describing the target's segments
+        config: String,
+        #[command(flatten)]
+        outputs: OutputOverrides,
+        /// Print the files that would be created, modified or are stale, and write nothing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the functions found by the prologue/rts heuristic
+    FindFuncs {
+        /// Binary to scan
+        bin: String,
+        /// Address the binary is loaded at
+        #[arg(long, value_parser = parse_address)]
+        vram: u32,
+        #[arg(long, value_enum, default_value_t = FuncsFormat::Text)]
+        format: FuncsFormat,
+    },
+    /// Disassemble the functions in a byte range of a binary
+    Disasm {
+        /// Binary to disassemble
+        bin: String,
+        /// File offsets to disassemble, as start..end
+        #[arg(long, value_parser = parse_range)]
+        range: (u64, u64),
+        /// Address the binary is loaded at
+        #[arg(long, value_parser = parse_address, default_value = "0")]
+        vram: u32,
+    },
+    /// Check the config and target, and optionally a rebuilt binary
+    Verify {
+        /// YAML config describing the target's segments
+        config: String,
+        /// Rebuilt binary to compare against the configured hashes
+        #[arg(long)]
+        build: Option<String>,
+    },
+    /// Print the segments and subsegments described by a config
+    Info {
+        /// YAML config describing the target's segments
+        config: String,
+    },
+    /// Report how much of the code has been decompiled
+    Progress {
+        /// YAML config describing the target's segments
+        config: String,
+        #[arg(long, value_enum, default_value_t = ProgressFormat::Text)]
+        format: ProgressFormat,
+        /// Count a function as decompiled when a copy listed in this mapping is
+        #[arg(long)]
+        shared: Option<String>,
+    },
+    /// Write library signatures for the functions in ELF or COFF-SH objects and archives
+    MakeSignatures {
+        /// Library the functions belong to, e.g. sgl, sbl or libc
+        #[arg(long)]
+        library: String,
+        /// Skip functions smaller than this many bytes, they match too often
+        #[arg(long, default_value_t = 16)]
+        min_size: usize,
+        /// Write the signatures to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+        /// Objects or archives to read
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /// Propose a symbol file for another build by matching its functions against a named build
+    MatchSymbols {
+        /// Binary the symbol file belongs to
+        from: String,
+        /// Address the named binary is loaded at
+        #[arg(long, value_parser = parse_address)]
+        from_vram: u32,
+        /// Symbol file of the named binary, in the user_syms.txt format
+        #[arg(long)]
+        symbols: String,
+        /// Binary to name
+        to: String,
+        /// Address the binary to name is loaded at
+        #[arg(long, value_parser = parse_address)]
+        to_vram: u32,
+        /// Leave out matches below this confidence, from 0 to 1
+        #[arg(long, default_value_t = 0.5)]
+        min_confidence: f64,
+        /// Write the proposed symbols to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Find functions copied between the segments and overlays of one or more configs
+    Duplicates {
+        /// YAML configs describing the targets
+        #[arg(required = true)]
+        configs: Vec<String>,
+        /// Also group copies of the same size sharing this fraction of instructions
+        #[arg(long, default_value_t = 1.0)]
+        min_similarity: f64,
+        /// Skip functions smaller than this many bytes, they are copies by chance
+        #[arg(long, default_value_t = 16)]
+        min_size: u32,
+        /// Write the groups as a shared-name mapping for `progress --shared`
+        #[arg(long)]
+        mapping: Option<String>,
+    },
+    /// Rank the functions most like one function across configured binaries
+    Similar {
+        /// Address of the function to look for, in the first config
+        #[arg(long, value_parser = parse_address)]
+        address: u32,
+        /// YAML configs whose functions are searched
+        #[arg(required = true)]
+        configs: Vec<String>,
+        /// Number of functions to show
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Compare emulator traces of executed addresses with the functions and data of a config
+    Coverage {
+        /// YAML config describing the target's segments
+        config: String,
+        /// Trace to read
+        #[arg(long = "trace", required = true)]
+        traces: Vec<String>,
+        /// How the traces are written
+        #[arg(long, value_enum, default_value_t = TraceFormat::Text)]
+        format: TraceFormat,
+        /// Binary traces hold little-endian addresses
+        #[arg(long)]
+        little_endian: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct OutputOverrides {
+    /// Write asm to this directory instead of the config's asm_path
+    #[arg(long)]
+    asm_path: Option<String>,
+    /// Write C files to this directory instead of the config's src_path
+    #[arg(long)]
+    src_path: Option<String>,
+    /// Write linker scripts to this directory instead of the config's ld_scripts_path
+    #[arg(long)]
+    ld_scripts_path: Option<String>,
+    /// Write symbol files to this directory instead of the config's syms_path
+    #[arg(long)]
+    syms_path: Option<String>,
+}
+
+impl OutputOverrides {
+    fn apply(self, options: &mut Options) {
+        let overrides = [
+            (self.asm_path, &mut options.asm_path),
+            (self.src_path, &mut options.src_path),
+            (self.ld_scripts_path, &mut options.ld_scripts_path),
+            (self.syms_path, &mut options.syms_path),
+        ];
+        for (value, option) in overrides {
+            if let Some(value) = value {
+                *option = value;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum TraceFormat {
+    /// A hex address at the start of each line
+    Text,
+    /// 32-bit addresses, big-endian unless --little-endian
+    Binary,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum FuncsFormat {
+    /// One start address per line
+    Text,
+    /// A function_ranges list for a subsegment
+    Yaml,
+}
+
+fn parse_address(value: &str) -> Result<u32, String> {
+    let parsed = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|error| format!("{}: {}", value, error))
+}
+
+fn parse_range(value: &str) -> Result<(u64, u64), String> {
+    let Some((start, end)) = value.split_once("..") else {
+        return Err(format!("{}: expected start..end", value));
+    };
+    let (start, end) = (parse_address(start)? as u64, parse_address(end)? as u64);
+    if end < start {
+        return Err(format!("{}: end is before start", value));
+    }
+    if (start | end) & 1 != 0 {
+        return Err(format!("{}: start and end must be 2-byte aligned", value));
+    }
+    Ok((start, end))
+}
+
+fn format_found_funcs(ranges: &[FunctionRange], vram: u32, format: FuncsFormat) -> String {
+    let mut output = String::new();
+    if format == FuncsFormat::Yaml {
+        output.push_str("function_ranges:\n");
+    }
+    for range in ranges {
+        match format {
+            FuncsFormat::Text => {
+                output.push_str(&format!("0x{:08X},\n", range.phys_start + vram));
+            }
+            FuncsFormat::Yaml => output.push_str(&format!(
+                "  - [0x{:X}, 0x{:X}] # {}\n",
+                range.phys_start,
+                range.phys_end,
+                naming::function(range.phys_start + vram)
+            )),
+        }
+    }
+    output
+}
+
+fn print_info(config: &Config, file_len: u64) {
+    println!(
+        "target: {} (0x{:X} bytes)",
+        config.options.target_path, file_len
+    );
+    let segments = config.segments.as_deref().unwrap_or_default();
+    for (i, segment) in segments.iter().enumerate() {
+        let end = segment_end(segments, i, file_len);
+        println!(
+            "segment {} ({}): 0x{:X}-0x{:X} vram 0x{:08X}",
+            segment.name, segment.segment_type, segment.start, end, segment.vram
+        );
+        if let Some(bss) = segment_bss(segments, i, file_len) {
+            println!("  bss: vram 0x{:08X} size 0x{:X}", bss.vram, bss.size);
+        }
+        for subsegment in segment.subsegments.iter().flatten() {
+            let end = subsegment.end.unwrap_or(file_len);
+            println!(
+                "  0x{:06X}-0x{:06X} {:>8} {} ({} bytes)",
+                subsegment.start,
+                end,
+                subsegment.segment_type.as_deref().unwrap_or("?"),
+                subsegment.file.as_deref().unwrap_or("?"),
+                end.saturating_sub(subsegment.start)
+            );
+        }
+    }
+}
+
+pub fn run(cli: Cli) -> Result<(), Error> {
+    match cli.command {
+        Commands::Split {
+            config,
+            outputs,
+            dry_run,
+        } => {
+            log::info!("Reading: {}", config);
+            let (mut config, file_contents) = load_config(&config)?;
+            outputs.apply(&mut config.options);
+            let mut plan = OutputPlan {
+                dry_run,
+                ..Default::default()
+            };
+            handle_segments(&file_contents, &config, &mut plan)?;
+            for (change, filename) in &plan.changes {
+                if dry_run {
+                    println!("{} {}", change, filename);
+                } else if *change == FileChange::Stale {
+                    log::warn!("{} is not generated by this split", filename);
+                } else if *change == FileChange::Remove {
+                    log::info!("removed {}", filename);
+                } else {
+                    log::debug!("{} {}", change, filename);
+                }
+            }
+            let count = |kind| {
+                plan.changes
+                    .iter()
+                    .filter(|(change, _)| *change == kind)
+                    .count()
+            };
+            let (removed, stale) = (count(FileChange::Remove), count(FileChange::Stale));
+            log::info!(
+                "{} files changed, {} unchanged, {} removed, {} stale",
+                plan.changes.len() - removed - stale,
+                plan.unchanged,
+                removed,
+                stale
+            );
+        }
+        Commands::FindFuncs { bin, vram, format } => {
+            let file_contents = read_binary(&bin)?;
+            let mut ranges = Vec::<FunctionRange>::new();
+            find_funcs(
+                &file_contents,
+                0,
+                file_contents.len() as u64 & !1,
+                &mut ranges,
+            );
+            print!("{}", format_found_funcs(&ranges, vram, format));
+        }
+        Commands::Disasm { bin, range, vram } => {
+            let file_contents = read_binary(&bin)?;
+            let (start, end) = range;
+            if end > file_contents.len() as u64 {
+                return Err(Error::Analysis(format!(
+                    "{}: range end 0x{:X} is beyond the end of the file (0x{:X} bytes)",
+                    bin,
+                    end,
+                    file_contents.len()
+                )));
+            }
+            let funcs = handle_code_section(
+                &file_contents,
+                start,
+                end,
+                vram as u64,
+                &HashMap::new(),
+                &Subsegment::default(),
+            );
+            for func in funcs.values() {
+                print!("{}", func.text);
+            }
+        }
+        Commands::Verify { config, build } => {
+            let (config, file_contents) = load_config(&config)?;
+            if let Some(build) = build {
+                let built = read_binary(&build)?;
+                if let Some(difference) = check_build(&config, Some(&file_contents), &built) {
+                    return Err(Error::BuildMismatch {
+                        path: build,
+                        difference,
+                    });
+                }
+                println!("{}: OK", build);
+            } else {
+                println!("{}: OK", config.options.target_path);
+            }
+        }
+        Commands::Info { config } => {
+            let (config, file_contents) = load_config(&config)?;
+            print_info(&config, file_contents.len() as u64);
+        }
+        Commands::Progress {
+            config,
+            format,
+            shared,
+        } => {
+            let (config, file_contents) = load_config(&config)?;
+            let (processed_sections, _) = process_segments(&file_contents, &config);
+            let copies = match shared {
+                Some(path) => decompiled_copies(&config, &path)?,
+                None => HashMap::new(),
+            };
+            let report = progress_report(&processed_sections, &find_includes(&config), &copies);
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            print!("{}", format_progress(&report, format, timestamp));
+        }
+        Commands::MakeSignatures {
+            library,
+            min_size,
+            output,
+            inputs,
+        } => {
+            let mut signatures = Vec::new();
+            for input in &inputs {
+                let contents = read_binary(input)?;
+                let functions = objects::read_functions(&contents)
+                    .map_err(|message| Error::Analysis(format!("{}: {}", input, message)))?;
+                signatures.extend(object_signatures(&library, &functions, min_size));
+            }
+            let contents = signatures::format_file(&signatures);
+            match output {
+                Some(path) => std::fs::write(&path, contents).with_path(&path)?,
+                None => print!("{}", contents),
+            }
+        }
+        Commands::MatchSymbols {
+            from,
+            from_vram,
+            symbols,
+            to,
+            to_vram,
+            min_confidence,
+            output,
+        } => {
+            if !Path::new(&symbols).exists() {
+                return Err(Error::Io {
+                    path: symbols,
+                    source: io::Error::from(io::ErrorKind::NotFound),
+                });
+            }
+            let matches = matching::match_functions(
+                &normalized_functions(&read_binary(&from)?, from_vram),
+                &normalized_functions(&read_binary(&to)?, to_vram),
+            );
+            let contents = format_matched_symbols(
+                &matches,
+                &read_user_symbols(&symbols),
+                &read_user_prototypes(&symbols),
+                min_confidence,
+            );
+            match output {
+                Some(path) => std::fs::write(&path, contents).with_path(&path)?,
+                None => print!("{}", contents),
+            }
+        }
+        Commands::Duplicates {
+            configs,
+            min_similarity,
+            min_size,
+            mapping,
+        } => {
+            let mut copies = Vec::new();
+            let mut words = Vec::new();
+            for config in &configs {
+                let (config, file_contents) = load_config(config)?;
+                for (copy, normalized) in shared_copies(&config, &file_contents, min_size) {
+                    copies.push(copy);
+                    words.push(normalized);
+                }
+            }
+            let clusters = matching::clusters(&words, min_similarity);
+            print!("{}", format_duplicates(&copies, &words, &clusters));
+            if let Some(path) = mapping {
+                std::fs::write(&path, format_shared_functions(&copies, &clusters))
+                    .with_path(&path)?;
+            }
+        }
+        Commands::Similar {
+            address,
+            configs,
+            top,
+        } => {
+            let mut listings = Vec::new();
+            for config in &configs {
+                let (config, file_contents) = load_config(config)?;
+                listings.extend(function_listings(&config, &file_contents));
+            }
+            let Some(wanted) = listings
+                .iter()
+                .position(|listing| listing.config == configs[0] && listing.address == address)
+            else {
+                return Err(Error::Analysis(format!(
+                    "{}: no function starts at 0x{:08X}",
+                    configs[0], address
+                )));
+            };
+            let wanted = listings.swap_remove(wanted);
+            print!("{}", format_similar(&wanted, &listings, top));
+        }
+        Commands::Coverage {
+            config,
+            traces,
+            format,
+            little_endian,
+        } => {
+            let (config, file_contents) = load_config(&config)?;
+            let (processed_sections, _) = process_segments(&file_contents, &config);
+            let mut pcs = Vec::new();
+            for path in &traces {
+                let contents = read_binary(path)?;
+                let trace = match format {
+                    TraceFormat::Text => trace::parse_text(&contents),
+                    TraceFormat::Binary => trace::parse_binary(&contents, little_endian),
+                };
+                let trace = trace.map_err(|(position, message)| {
+                    Error::Analysis(format!("{}:{}: {}", path, position, message))
+                })?;
+                pcs.extend(trace);
+            }
+            let coverage = trace::coverage(&pcs, |pc| {
+                let section = processed_sections.iter().find(|section| {
+                    pc >= section.vaddr as u32 && pc < (section.vbase + section_end(section)) as u32
+                })?;
+                let phys = (pc - section.vbase as u32) as usize;
+                let bytes = file_contents.get(phys..phys + 2)?;
+                Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+            });
+            print!("{}", format_coverage(&processed_sections, &coverage));
+        }
+    }
+    Ok(())
+}
+
+// The functions find_funcs sees in a whole binary, by address, normalized
+// for matching against another build.
+fn normalized_functions(file_contents: &Vec<u8>, vram: u32) -> Vec<(u32, Vec<u16>)> {
+    let mut ranges = Vec::<FunctionRange>::new();
+    find_funcs(
+        file_contents,
+        0,
+        file_contents.len() as u64 & !1,
+        &mut ranges,
+    );
+    ranges
+        .iter()
+        .filter_map(|range| {
+            // phys_end is the rts, keep its delay slot
+            let bytes =
+                file_contents.get(range.phys_start as usize..range.phys_end as usize + 2)?;
+            Some((range.phys_start + vram, matching::normalize(bytes)))
+        })
+        .collect()
+}
+
+// A user_syms.txt for the other build. Each symbol is preceded by the
+// address it came from and the match confidence, so low ones can be checked.
+fn format_matched_symbols(
+    matches: &[matching::Match],
+    symbols: &HashMap<u32, String>,
+    prototypes: &HashMap<u32, String>,
+    min_confidence: f64,
+) -> String {
+    let mut output = String::new();
+    for found in matches {
+        let Some(name) = symbols.get(&found.from) else {
+            continue;
+        };
+        if found.confidence < min_confidence {
+            log::debug!(
+                "{}: 0x{:08X} only matches with confidence {:.2}",
+                name,
+                found.to,
+                found.confidence
+            );
+            continue;
+        }
+        output.push_str(&format!(
+            "// 0x{:08X}, confidence {:.2}\n",
+            found.from, found.confidence
+        ));
+        match prototypes.get(&found.from) {
+            Some(prototype) => output.push_str(&format!(
+                "{} = 0x{:08X}; // {}\n",
+                name, found.to, prototype
+            )),
+            None => output.push_str(&format!("{} = 0x{:08X};\n", name, found.to)),
+        }
+    }
+    output
+}
+
+fn format_duplicates(
+    copies: &[SharedCopy],
+    words: &[Vec<u16>],
+    clusters: &[Vec<(usize, f64)>],
+) -> String {
+    let mut output = String::new();
+    for cluster in clusters {
+        output.push_str(&format!(
+            "0x{:X} bytes, {} copies:\n",
+            words[cluster[0].0].len() * 2,
+            cluster.len()
+        ));
+        for &(index, similarity) in cluster {
+            let copy = &copies[index];
+            output.push_str(&format!(
+                "  {} {} {} 0x{:08X}",
+                copy.config, copy.segment, copy.function, copy.address
+            ));
+            if similarity < 1.0 {
+                output.push_str(&format!(" ({:.0}% alike)", similarity * 100.0));
+            }
+            output.push('\n');
+        }
+    }
+    output.push_str(&format!(
+        "{} functions in {} groups\n",
+        clusters.iter().map(Vec::len).sum::<usize>(),
+        clusters.len()
+    ));
+    output
+}
+
+// A function's instructions as the disassembler prints them, for
+// comparing functions side by side.
+struct FunctionListing {
+    config: String,
+    segment: String,
+    name: String,
+    address: u32,
+    instructions: Vec<String>,
+    // the instructions with PC-relative fields cleared, to compare
+    normalized: Vec<u16>,
+}
+
+impl FunctionListing {
+    fn mnemonics(&self) -> Vec<&str> {
+        self.instructions
+            .iter()
+            .map(|instruction| instruction.split(' ').next().unwrap_or_default())
+            .collect()
+    }
+}
+
+fn function_listings(config: &Config, file_contents: &Vec<u8>) -> Vec<FunctionListing> {
+    let (processed_sections, _) = process_segments(file_contents, config);
+    let mut listings = Vec::new();
+    for section in processed_sections.iter().filter(|section| section.is_code) {
+        for func in section
+            .disassembled_funcs
+            .values()
+            .filter(|func| !func.data)
+        {
+            let address = func.addr + section.vbase as u32;
+            let Some(bytes) = file_contents.get(func.addr as usize..func.end as usize) else {
+                continue;
+            };
+            let instructions = bytes
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(index, word)| {
+                    let mut string = String::new();
+                    sh2_disasm(
+                        address + index as u32 * 2,
+                        u16::from_be_bytes([word[0], word[1]]).into(),
+                        true,
+                        &mut string,
+                        &HashMap::new(),
+                        &HashMap::new(),
+                    );
+                    string
+                })
+                .collect();
+            listings.push(FunctionListing {
+                config: config.filename.clone(),
+                segment: section.segment.clone(),
+                name: naming::function(address),
+                address,
+                instructions,
+                normalized: matching::normalize(bytes),
+            });
+        }
+    }
+    listings
+}
+
+// The top candidates by mnemonic trigrams, each beside the wanted function.
+// Lines whose instructions differ, other than in PC-relative targets, are
+// marked with `*`.
+fn format_similar(wanted: &FunctionListing, candidates: &[FunctionListing], top: usize) -> String {
+    let wanted_mnemonics = wanted.mnemonics();
+    let mut ranked: Vec<(f64, &FunctionListing)> = candidates
+        .iter()
+        .map(|candidate| {
+            let score = matching::ngram_similarity(&wanted_mnemonics, &candidate.mnemonics(), 3);
+            (score, candidate)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut output = format!(
+        "{} 0x{:08X} in {} {}, {} instructions\n",
+        wanted.name,
+        wanted.address,
+        wanted.config,
+        wanted.segment,
+        wanted.instructions.len()
+    );
+    for (rank, (score, candidate)) in ranked.iter().take(top).enumerate() {
+        output.push_str(&format!(
+            "\n{}. {:.2} {} 0x{:08X} in {} {}\n",
+            rank + 1,
+            score,
+            candidate.name,
+            candidate.address,
+            candidate.config,
+            candidate.segment
+        ));
+        let lines = wanted.instructions.len().max(candidate.instructions.len());
+        for line in 0..lines {
+            let left = wanted.instructions.get(line);
+            let right = candidate.instructions.get(line);
+            let same = wanted.normalized.get(line) == candidate.normalized.get(line);
+            let column = |address: u32, instruction: Option<&String>| match instruction {
+                Some(instruction) => format!("{:08X}  {}", address + line as u32 * 2, instruction),
+                None => String::new(),
+            };
+            output.push_str(
+                format!(
+                    "{} {:<40} {}",
+                    if same { ' ' } else { '*' },
+                    column(wanted.address, left),
+                    column(candidate.address, right)
+                )
+                .trim_end(),
+            );
+            output.push('\n');
+        }
+    }
+    output
+}
+
+// A processed section's end offset; an odd end is inclusive.
+fn section_end(section: &ProcessedSection) -> u64 {
+    section.end + (section.end & 1)
+}
+
+// What a trace shows about each subsegment. Calls into code that starts no
+// function and code run inside data are also reported as diagnostics.
+fn format_coverage(sections: &[ProcessedSection], coverage: &trace::Coverage) -> String {
+    let mut output = String::new();
+    let range = |(start, end): (u32, u32)| format!("0x{:08X}-0x{:08X}", start, end);
+    for section in sections {
+        let start = section.vaddr as u32;
+        let end = (section.vbase + section_end(section)) as u32;
+        output.push_str(&format!(
+            "{} {} ({}) {}: ",
+            section.segment,
+            section.file,
+            section.section_type,
+            range((start, end))
+        ));
+        if !section.is_code {
+            let ran = coverage.ran(start, end);
+            output.push_str(&format!("{} ranges ran\n", ran.len()));
+            for ran in ran {
+                diagnose(
+                    "trace-ran-data",
+                    ran.0,
+                    format!("{} ran but is data in {}", range(ran), section.file),
+                );
+                output.push_str(&format!("  ran {}\n", range(ran)));
+            }
+            continue;
+        }
+
+        let functions: BTreeSet<u32> = section
+            .disassembled_funcs
+            .values()
+            .filter(|func| !func.data)
+            .map(|func| func.addr + section.vbase as u32)
+            .collect();
+        let ran_bytes: usize = coverage
+            .ran(start, end)
+            .iter()
+            .map(|(start, end)| (end - start) as usize)
+            .sum();
+        output.push_str(&format!(
+            "0x{:X}/0x{:X} bytes ran, {}/{} functions\n",
+            ran_bytes,
+            end - start,
+            functions
+                .iter()
+                .filter(|address| coverage.executed.contains(address))
+                .count(),
+            functions.len()
+        ));
+        for &entry in coverage.entries.range(start..end) {
+            if !functions.contains(&entry) {
+                diagnose(
+                    "trace-entry",
+                    entry,
+                    format!(
+                        "{} is called but starts no function",
+                        naming::function(entry)
+                    ),
+                );
+                output.push_str(&format!(
+                    "  0x{:08X} is called but starts no function\n",
+                    entry
+                ));
+            }
+        }
+        for untouched in coverage.untouched(start, end) {
+            output.push_str(&format!("  untouched {}\n", range(untouched)));
+        }
+    }
+    output
+}
+
+// Signatures for an object's functions of at least min_size bytes.
+fn object_signatures(
+    library: &str,
+    functions: &[objects::ObjectFunction],
+    min_size: usize,
+) -> Vec<signatures::Signature> {
+    functions
+        .iter()
+        .filter(|function| {
+            let big_enough = function.bytes.len() >= min_size;
+            if !big_enough {
+                log::debug!("{}: {} bytes, skipped", function.name, function.bytes.len());
+            }
+            big_enough
+        })
+        .map(|function| {
+            signatures::Signature::from_function(
+                library,
+                &naming::c_name(&function.name),
+                &function.bytes,
+                &function.fixups,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::words_bytes;
+
+    #[test]
+    fn test_cli_parses_subcommands() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "rust-dis",
+            "find-funcs",
+            "game.bin",
+            "--vram",
+            "0x06066000",
+            "--format",
+            "yaml",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::FindFuncs {
+                vram: 0x06066000,
+                format: FuncsFormat::Yaml,
+                ..
+            }
+        ));
+
+        let cli =
+            Cli::try_parse_from(["rust-dis", "disasm", "game.bin", "--range", "0x100..0x180"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Disasm {
+                range: (0x100, 0x180),
+                vram: 0,
+                ..
+            }
+        ));
+
+        assert!(Cli::try_parse_from(["rust-dis"]).is_err());
+        assert!(Cli::try_parse_from(["rust-dis", "find-funcs", "game.bin"]).is_err());
+        assert!(parse_range("0x180..0x100").is_err());
+        assert_eq!(
+            parse_range("0x100..0x181"),
+            Err("0x100..0x181: start and end must be 2-byte aligned".to_string())
+        );
+    }
+
+    #[test]
+    fn test_split_output_overrides_replace_config_paths() {
+        let cli = Cli::try_parse_from([
+            "rust-dis",
+            "split",
+            "config.yaml",
+            "--asm-path",
+            "out/asm",
+            "--syms-path",
+            "out/syms",
+        ])
+        .unwrap();
+        let Commands::Split { outputs, .. } = cli.command else {
+            panic!("expected split");
+        };
+        let mut options: Options = serde_yaml::from_str(
+            "target_path: t.bin\nasm_path: asm\nsrc_path: src\nld_scripts_path: ld\nsyms_path: syms\ndecomp_empty_funcs: false\n",
+        )
+        .unwrap();
+
+        outputs.apply(&mut options);
+
+        assert_eq!(options.asm_path, "out/asm");
+        assert_eq!(options.src_path, "src");
+        assert_eq!(options.ld_scripts_path, "ld");
+        assert_eq!(options.syms_path, "out/syms");
+    }
+
+    #[test]
+    fn test_find_funcs_yaml_output() {
+        let ranges = vec![FunctionRange {
+            phys_start: 0x10,
+            phys_end: 0x2a,
+            is_data: false,
+        }];
+
+        assert_eq!(
+            format_found_funcs(&ranges, 0x06066000, FuncsFormat::Text),
+            "0x06066010,\n"
+        );
+        assert_eq!(
+            format_found_funcs(&ranges, 0x06066000, FuncsFormat::Yaml),
+            "function_ranges:\n  - [0x10, 0x2A] # func_06066010\n"
+        );
+    }
+
+    #[test]
+    fn test_similar_functions_rank_by_mnemonics_side_by_side() {
+        let listing = |config: &str, address: u32, instructions: &[(u16, &str)]| {
+            let bytes: Vec<u8> = instructions
+                .iter()
+                .flat_map(|(word, _)| word.to_be_bytes())
+                .collect();
+            FunctionListing {
+                config: config.to_string(),
+                segment: "main".to_string(),
+                name: naming::function(address),
+                address,
+                instructions: instructions.iter().map(|(_, i)| i.to_string()).collect(),
+                normalized: matching::normalize(&bytes),
+            }
+        };
+        let wanted = listing(
+            "a.yaml",
+            0x06004000,
+            &[
+                (0x4f22, "sts.l pr, @-r15"),
+                (0xe410, "mov #0x10, r4"),
+                (0xb010, "bsr 0x06004026"),
+                (0x0009, "nop"),
+                (0x4f26, "lds.l @r15+, pr"),
+                (0x000b, "rts"),
+                (0x0009, "nop"),
+            ],
+        );
+        let candidates = [
+            listing(
+                "b.yaml",
+                0x06100000,
+                &[
+                    (0x2448, "tst r4, r4"),
+                    (0x8901, "bt 0x06100008"),
+                    (0x000b, "rts"),
+                    (0x0009, "nop"),
+                ],
+            ),
+            listing(
+                "b.yaml",
+                0x06104000,
+                &[
+                    (0x4f22, "sts.l pr, @-r15"),
+                    (0xe420, "mov #0x20, r4"),
+                    (0xb010, "bsr 0x06104026"),
+                    (0x0009, "nop"),
+                    (0x4f26, "lds.l @r15+, pr"),
+                    (0x000b, "rts"),
+                    (0x0009, "nop"),
+                ],
+            ),
+        ];
+
+        let output = format_similar(&wanted, &candidates, 1);
+        assert!(output.starts_with("func_06004000 0x06004000 in a.yaml main, 7 instructions\n"));
+        assert!(output.contains("\n1. 1.00 func_06104000 0x06104000 in b.yaml main\n"));
+        assert!(output.contains(&format!(
+            "* {:<40} 06104002  mov #0x20, r4\n",
+            "06004002  mov #0x10, r4"
+        )));
+        // the call is the same, only its absolute target moved
+        assert!(output.contains(&format!(
+            "  {:<40} 06104004  bsr 0x06104026\n",
+            "06004004  bsr 0x06004026"
+        )));
+        assert!(!output.contains("func_06100000"));
+    }
+
+    #[test]
+    fn test_trace_coverage_flags_missed_functions_and_executed_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let bytes = words_bytes(&[
+            0x4f22, 0xb005, 0x0009, 0x4f26, 0x000b, 0x0009, // calls 0x06000010
+            0x0009, 0x0009, // never runs
+            0x000b, 0x0009, // not in function_ranges
+            0x0009, 0x000b, // data that runs
+        ]);
+        std::fs::write(path("t.bin"), &bytes).unwrap();
+        std::fs::write(
+            path("c.yaml"),
+            format!(
+                r#"options:
+  target_path: {0}/t.bin
+  asm_path: {0}/asm
+  src_path: {0}/src
+  ld_scripts_path: {0}/build
+  syms_path: {0}/build
+  decomp_empty_funcs: false
+segments:
+  - name: main
+    type: code
+    start: 0
+    vram: 0x06000000
+    subsegments:
+      - {{start: 0, end: 0x13, type: c, file: main, function_ranges: [[0, 0xb]]}}
+      - {{start: 0x14, end: 0x17, type: data, file: main}}
+"#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        let (config, file_contents) = load_config(&path("c.yaml")).unwrap();
+        let (sections, _) = process_segments(&file_contents, &config);
+
+        let pcs = trace::parse_text(
+            b"06000000\n06000002\n06000004\n26000010\n06000012\n06000006\n06000008\n0600000A\n06000014\n",
+        )
+        .unwrap();
+        let coverage = trace::coverage(&pcs, |pc| {
+            let phys = (pc - 0x06000000) as usize;
+            let bytes = file_contents.get(phys..phys + 2)?;
+            Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+        });
+        assert_eq!(
+            format_coverage(&sections, &coverage),
+            "main main (c) 0x06000000-0x06000014: 0x10/0x14 bytes ran, 1/1 functions\n\
+             \x20 0x06000010 is called but starts no function\n\
+             \x20 untouched 0x0600000C-0x06000010\n\
+             main main (data) 0x06000014-0x06000018: 1 ranges ran\n\
+             \x20 ran 0x06000014-0x06000016\n"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+// Linker scripts, generated entirely from the YAML so they can be ephemeral.
+
+use crate::{BssRange, Segment};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, PartialEq)]
+pub struct LinkerInput {
+    pub start: u64,
+    pub object: String,
+    pub section: String,
+}
+
+pub fn linker_inputs(segment: &Segment) -> Vec<LinkerInput> {
+    let mut seen = HashSet::new();
+    let mut inputs = Vec::new();
+    let mut legacy_text_starts = HashMap::new();
+    let mut legacy_c_files = HashSet::new();
+
+    if let Some(subsegments) = &segment.subsegments {
+        for subsegment in subsegments {
+            if matches!(subsegment.segment_type.as_deref(), Some("data") | Some("c")) {
+                if let Some(file) = &subsegment.file {
+                    legacy_text_starts
+                        .entry(file.clone())
+                        .and_modify(|start: &mut u64| *start = (*start).min(subsegment.start))
+                        .or_insert(subsegment.start);
+                    if subsegment.segment_type.as_deref() == Some("c") {
+                        legacy_c_files.insert(file.clone());
+                    }
+                }
+            }
+        }
+        for subsegment in subsegments {
+            let Some(file) = &subsegment.file else {
+                continue;
+            };
+            let section = match subsegment.segment_type.as_deref() {
+                Some("c") => ".text",
+                Some("data") if !legacy_c_files.contains(file) => ".text",
+                Some(".text") => ".text",
+                Some(".data") => ".data",
+                Some(".rodata") => ".rodata",
+                Some(".bss") => ".bss",
+                Some(".sbss") => ".sbss",
+                _ => continue,
+            };
+            if seen.insert((file.clone(), section)) {
+                inputs.push(LinkerInput {
+                    start: if matches!(subsegment.segment_type.as_deref(), Some("c") | Some("data"))
+                    {
+                        legacy_text_starts[file]
+                    } else {
+                        subsegment.start
+                    },
+                    object: format!("{}.o", file),
+                    section: section.to_string(),
+                });
+            }
+        }
+    }
+
+    inputs
+}
+
+// Group inputs by the configured section order, each section in address
+// order. Sections missing from the list follow the listed ones.
+pub fn order_linker_inputs(inputs: &mut [LinkerInput], section_order: &[String]) {
+    if section_order.is_empty() {
+        return;
+    }
+    inputs.sort_by_key(|input| (section_rank(&input.section, section_order), input.start));
+}
+
+pub fn section_rank(section: &str, section_order: &[String]) -> usize {
+    section_order
+        .iter()
+        .position(|listed| listed == section)
+        .unwrap_or(section_order.len())
+}
+
+pub fn gen_ld_script(
+    zero_prefix: &str,
+    addr: &str,
+    subalign: u64,
+    obj_path: &str,
+    check_layout: bool,
+    inputs: &[LinkerInput],
+    bss: Option<&BssRange>,
+) -> String {
+    let mut code = String::new();
+    let is_noload = |input: &&LinkerInput| matches!(input.section.as_str(), ".bss" | ".sbss");
+    let object_path = |input: &LinkerInput| {
+        if obj_path.is_empty() {
+            input.object.clone()
+        } else {
+            format!("{}/{}", obj_path.trim_end_matches('/'), input.object)
+        }
+    };
+
+    code.push_str("SECTIONS\n{\n");
+    code.push_str("    __romPos = 0;\n");
+    code.push_str("    _gp = 0x0;\n");
+    code.push_str(&format!("    {}_ROM_START = __romPos;\n", zero_prefix));
+    code.push_str(&format!(
+        "    {}_VRAM = ADDR(.{});\n",
+        zero_prefix, zero_prefix
+    ));
+    code.push_str(&format!(
+        "    .{} 0x{} : AT({}_ROM_START) SUBALIGN({})\n    {{\n",
+        zero_prefix, addr, zero_prefix, subalign
+    ));
+    code.push_str(&format!("        {}_TEXT_START = .;\n", zero_prefix));
+    for input in inputs.iter().filter(|input| !is_noload(input)) {
+        if check_layout {
+            code.push_str(&format!(
+                "        ASSERT(. - ADDR(.{}) == 0x{:X}, \"{} {} starts at the wrong offset\");\n",
+                zero_prefix, input.start, input.object, input.section,
+            ));
+        }
+        code.push_str(&format!(
+            "        {}({});\n",
+            object_path(input),
+            input.section
+        ));
+    }
+    code.push_str(&format!("        {}_TEXT_END = .;\n", zero_prefix));
+    code.push_str(&format!(
+        "        {}_TEXT_SIZE = ABSOLUTE({}_TEXT_END - {}_TEXT_START);\n    }}\n",
+        zero_prefix, zero_prefix, zero_prefix
+    ));
+    code.push_str(&format!("    __romPos += SIZEOF(.{});\n", zero_prefix));
+    code.push_str("    __romPos = ALIGN(__romPos, 16);\n");
+    code.push_str(&format!("    {}_ROM_END = __romPos;\n", zero_prefix));
+    code.push_str(&format!("    {}_VRAM_END = .;\n", zero_prefix));
+
+    let bss_inputs = inputs.iter().filter(is_noload).collect::<Vec<_>>();
+    if bss.is_some() || !bss_inputs.is_empty() {
+        let addr = bss.map_or(String::new(), |bss| format!(" 0x{:08X}", bss.vram));
+        code.push_str(&format!(
+            "\n    .{}_bss{} (NOLOAD) : SUBALIGN({})\n    {{\n",
+            zero_prefix, addr, subalign
+        ));
+        code.push_str(&format!("        {}_BSS_START = .;\n", zero_prefix));
+        for input in bss_inputs {
+            code.push_str(&format!(
+                "        {}({});\n",
+                object_path(input),
+                input.section
+            ));
+        }
+        if let Some(bss) = bss {
+            code.push_str(&format!(
+                "        . = {}_BSS_START + 0x{:X};\n",
+                zero_prefix, bss.size
+            ));
+        }
+        code.push_str(&format!("        {}_BSS_END = .;\n", zero_prefix));
+        code.push_str(&format!(
+            "        {}_BSS_SIZE = ABSOLUTE({}_BSS_END - {}_BSS_START);\n    }}\n",
+            zero_prefix, zero_prefix, zero_prefix
+        ));
+    }
+
+    code.push_str("\n    /DISCARD/ :\n    {\n");
+    code.push_str("        *(*);\n    }\n");
+    code.push_str("}");
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{print_diff, Config, Subsegment};
+
+    #[test]
+    fn test_ld_script() {
+        let expected = r#"SECTIONS
+{
+    __romPos = 0;
+    _gp = 0x0;
+    zero_ROM_START = __romPos;
+    zero_VRAM = ADDR(.zero);
+    .zero 0x06004080 : AT(zero_ROM_START) SUBALIGN(2)
+    {
+        zero_TEXT_START = .;
+        build/saturn/zero.o(.text);
+        zero_TEXT_END = .;
+        zero_TEXT_SIZE = ABSOLUTE(zero_TEXT_END - zero_TEXT_START);
+    }
+    __romPos += SIZEOF(.zero);
+    __romPos = ALIGN(__romPos, 16);
+    zero_ROM_END = __romPos;
+    zero_VRAM_END = .;
+
+    /DISCARD/ :
+    {
+        *(*);
+    }
+}"#;
+
+        let actual = gen_ld_script(
+            "zero",
+            "06004080",
+            2,
+            "build/saturn",
+            false,
+            &[LinkerInput {
+                start: 0,
+                object: "zero.o".to_string(),
+                section: ".text".to_string(),
+            }],
+            None,
+        );
+        print_diff(expected.to_string(), actual.clone());
+        assert!(expected == actual);
+    }
+
+    #[test]
+    fn test_linker_inputs_are_ordered_and_deduplicated() {
+        let segment = Segment {
+            name: "zero".to_string(),
+            segment_type: "code".to_string(),
+            start: 0,
+            vram: 0x06004080,
+            subalign: Some(4),
+            bss_size: None,
+            bss_vram: None,
+            sha1: None,
+            subsegments: Some(vec![
+                Subsegment {
+                    start: 8,
+                    end: Some(8),
+                    segment_type: Some("data".to_string()),
+                    file: Some("zero".to_string()),
+                    function_ranges: Vec::new(),
+                    data_ranges: Vec::new(),
+                    gbr: Vec::new(),
+                },
+                Subsegment {
+                    start: 0,
+                    end: Some(16),
+                    segment_type: Some("c".to_string()),
+                    file: Some("zero".to_string()),
+                    function_ranges: Vec::new(),
+                    data_ranges: Vec::new(),
+                    gbr: Vec::new(),
+                },
+                Subsegment {
+                    start: 16,
+                    end: Some(24),
+                    segment_type: Some("c".to_string()),
+                    file: Some("lib/spr/spr_1c".to_string()),
+                    function_ranges: Vec::new(),
+                    data_ranges: Vec::new(),
+                    gbr: Vec::new(),
+                },
+                Subsegment {
+                    start: 24,
+                    end: Some(32),
+                    segment_type: Some("c".to_string()),
+                    file: Some("zero".to_string()),
+                    function_ranges: Vec::new(),
+                    data_ranges: Vec::new(),
+                    gbr: Vec::new(),
+                },
+            ]),
+        };
+
+        assert_eq!(
+            linker_inputs(&segment),
+            vec![
+                LinkerInput {
+                    start: 0,
+                    object: "zero.o".to_string(),
+                    section: ".text".to_string(),
+                },
+                LinkerInput {
+                    start: 16,
+                    object: "lib/spr/spr_1c.o".to_string(),
+                    section: ".text".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_splat_style_named_sections_generate_in_yaml_order() {
+        let yaml = r#"
+options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  obj_path: build
+  ld_scripts_path: build
+  syms_path: build
+  check_layout: true
+  decomp_empty_funcs: false
+segments:
+  - name: fixture
+    type: code
+    start: 0
+    vram: 0x06010000
+    subalign: 2
+    subsegments:
+      - [0x0, .data, header]
+      - [0x8, c, main]
+      - [0x20, .data, animations]
+      - [0x28, .rodata, tables]
+      - start: 0x30
+        type: data
+        file: raw_tail
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let segment = &config.segments.as_ref().unwrap()[0];
+        let inputs = linker_inputs(segment);
+
+        assert_eq!(
+            inputs,
+            vec![
+                LinkerInput {
+                    start: 0,
+                    object: "header.o".to_string(),
+                    section: ".data".to_string()
+                },
+                LinkerInput {
+                    start: 8,
+                    object: "main.o".to_string(),
+                    section: ".text".to_string()
+                },
+                LinkerInput {
+                    start: 0x20,
+                    object: "animations.o".to_string(),
+                    section: ".data".to_string()
+                },
+                LinkerInput {
+                    start: 0x28,
+                    object: "tables.o".to_string(),
+                    section: ".rodata".to_string()
+                },
+                LinkerInput {
+                    start: 0x30,
+                    object: "raw_tail.o".to_string(),
+                    section: ".text".to_string()
+                },
+            ]
+        );
+
+        let script = gen_ld_script("fixture", "06010000", 2, "build", true, &inputs, None);
+        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x0"));
+        assert!(script.contains("build/header.o(.data);"));
+        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x8"));
+        assert!(script.contains("build/main.o(.text);"));
+        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x20"));
+        assert!(script.contains("build/animations.o(.data);"));
+        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x28"));
+        assert!(script.contains("build/tables.o(.rodata);"));
+        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x30"));
+        assert!(script.contains("build/raw_tail.o(.text);"));
+    }
+
+    #[test]
+    fn test_section_order_groups_linker_inputs() {
+        let input = |start: u64, object: &str, section: &str| LinkerInput {
+            start,
+            object: object.to_string(),
+            section: section.to_string(),
+        };
+        let mut inputs = vec![
+            input(24, "b.o", ".text"),
+            input(8, "a.o", ".data"),
+            input(16, "b.o", ".rodata"),
+            input(0, "a.o", ".text"),
+        ];
+
+        order_linker_inputs(&mut inputs, &[".rodata".to_string(), ".text".to_string()]);
+
+        assert_eq!(
+            inputs,
+            vec![
+                input(16, "b.o", ".rodata"),
+                input(0, "a.o", ".text"),
+                input(24, "b.o", ".text"),
+                input(8, "a.o", ".data"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ld_script_emits_noload_bss_section() {
+        let inputs = vec![
+            LinkerInput {
+                start: 0,
+                object: "main.o".to_string(),
+                section: ".text".to_string(),
+            },
+            LinkerInput {
+                start: 0x80,
+                object: "main.o".to_string(),
+                section: ".bss".to_string(),
+            },
+        ];
+        let bss = BssRange {
+            vram: 0x06004080,
+            size: 0x100,
+        };
+
+        let script = gen_ld_script("zero", "06004000", 2, "build", true, &inputs, Some(&bss));
+
+        assert!(script.contains("ASSERT(. - ADDR(.zero) == 0x0"));
+        assert!(!script.contains("ASSERT(. - ADDR(.zero) == 0x80"));
+        assert!(script.contains(
+            "    .zero_bss 0x06004080 (NOLOAD) : SUBALIGN(2)\n    {\n        zero_BSS_START = .;\n        build/main.o(.bss);\n        . = zero_BSS_START + 0x100;\n        zero_BSS_END = .;\n"
+        ));
+        assert!(script.find("zero_VRAM_END").unwrap() < script.find("zero_BSS_START").unwrap());
+    }
+}
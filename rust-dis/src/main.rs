@@ -1,16 +1,39 @@
 // why is 14c0 made into data?
 
+mod cli;
+mod linker;
+mod matching;
+mod naming;
+mod ninja;
+mod objects;
+mod output;
+mod progress;
+mod saturn;
+mod signatures;
+mod trace;
+
+use cli::{run, Cli};
+use linker::{gen_ld_script, linker_inputs, order_linker_inputs, section_rank};
+use ninja::gen_build_ninja;
+use output::OutputPlan;
+
+use clap::Parser;
 use regex::Regex;
 use serde::de::Deserializer;
 use serde_derive::Deserialize;
 use serde_yaml;
+use similar::{ChangeTag, TextDiff};
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
+use std::fs;
 use std::fs::File;
-use std::io::BufReader;
-use std::io::{self, BufRead, Read};
+use std::io::{self, Read, Write};
 use std::path::Path;
+use std::process::{Command, ExitCode};
+use tempfile::NamedTempFile;
 
 struct DataLabel {
     size: u32,
@@ -892,7 +915,7 @@ fn is_beyond_last_func(i: u32, ranges: &Vec<FunctionRange>) -> (bool, u32) {
 }
 
 fn add_label(addr: u32, branch_labels: &mut HashMap<u32, String>) {
-    let label = format!(".L{:08X}", addr);
+    let label = naming::label(addr);
     branch_labels.entry(addr).or_insert(label);
 }
 
@@ -982,7 +1005,7 @@ fn find_jump_tables(
             continue;
         }
 
-        let table_label = format!(".Ljtbl_{:08X}", table_addr);
+        let table_label = naming::jump_table(table_addr);
         let mut targets = Vec::new();
         for index in 0..entry_count {
             let entry_offset = (table_offset + index * 2) as usize;
@@ -1030,7 +1053,7 @@ fn remove_jump_table_internal_labels(
 }
 
 fn add_data_label(source: u32, addr: u32, size: u32, data_labels: &mut HashMap<u32, DataLabel>) {
-    let the_label = format!(".Ldat_{:08X}", addr);
+    let the_label = naming::data_label(addr);
     let data_label = DataLabel {
         size,
         label: the_label,
@@ -1103,17 +1126,17 @@ fn find_data_labels(v_addr: u32, op: u32, data_labels: &mut HashMap<u32, DataLab
         let target = ((op & 0xff) * 4 + 4 + v_addr) & 0xfffffffc;
 
         if v_addr == 0x6d94 {
-            println!("problem {:08X}", target);
+            log::debug!("problem {:08X}", target);
             // return;
         }
         // TODO fixme this shouln't be marked as data
         if target == 0x14C0 {
-            println!("problem {:08X}", v_addr);
+            log::debug!("problem {:08X}", v_addr);
             return;
         }
 
         if target == 0x35c8 {
-            println!("problem {:08X}", v_addr);
+            log::debug!("problem {:08X}", v_addr);
             return;
         }
         add_data_label(v_addr, target, 4, data_labels);
@@ -1144,6 +1167,98 @@ fn literal_feeds_call(file_contents: &Vec<u8>, source: u32, virtual_base_addr: u
     false
 }
 
+// A BIOS call loads a routine pointer from the vector area and jumps through
+// it: `mov.l .Ldat,rN`, `mov.l @rN,rM`, `jsr @rM`. Returns the address of the
+// jsr when the load at source starts one.
+fn bios_call(file_contents: &[u8], source: u32, virtual_base_addr: u64) -> Option<u32> {
+    let source_offset = source.checked_sub(virtual_base_addr as u32)? as usize;
+    let op_at = |offset: usize| {
+        file_contents
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as u32)
+    };
+    let load = op_at(source_offset)?;
+    if load & 0xf000 != 0xd000 {
+        return None;
+    }
+    let vector_register = (load >> 8) & 0xf;
+    let mut pointer_register = None;
+    for offset in ((source_offset + 2)..(source_offset + 34)).step_by(2) {
+        let op = op_at(offset)?;
+        if op & 0xf00f == 0x6002 && (op >> 4) & 0xf == vector_register {
+            pointer_register = Some((op >> 8) & 0xf);
+        } else if op & 0xf0ff == 0x400b && Some((op >> 8) & 0xf) == pointer_register {
+            return Some(offset as u32 + virtual_base_addr as u32);
+        }
+    }
+    None
+}
+
+// Known register constants within a function, enough to follow
+// `mov.l .Ldat,rN` / `ldc rN,gbr` and name GBR-relative operands. Anything
+// that might write a register forgets it.
+#[derive(Default)]
+struct GbrTracker {
+    registers: [Option<u32>; 16],
+    gbr: Option<u32>,
+}
+
+impl GbrTracker {
+    fn enter_function(&mut self, gbr: Option<u32>) {
+        self.registers = [None; 16];
+        self.gbr = gbr;
+    }
+
+    // a branch target can be reached with other register values
+    fn enter_label(&mut self) {
+        self.registers = [None; 16];
+    }
+
+    fn comment(&self, op: u32, user_symbols: &HashMap<u32, String>) -> Option<String> {
+        let describe = |addr: u32| match literal_symbol(addr, user_symbols) {
+            Some(symbol) => format!("0x{:08X} {}", addr, symbol),
+            None => format!("0x{:08X}", addr),
+        };
+        let disp = op & 0xff;
+        match op >> 8 {
+            0x40..=0x4f if op & 0xff == 0x1e => self.registers[((op >> 8) & 0xf) as usize]
+                .map(|value| format!("gbr = {}", describe(value))),
+            0xc0 | 0xc4 => self.gbr.map(|gbr| describe(gbr.wrapping_add(disp))),
+            0xc1 | 0xc5 => self.gbr.map(|gbr| describe(gbr.wrapping_add(disp * 2))),
+            0xc2 | 0xc6 => self.gbr.map(|gbr| describe(gbr.wrapping_add(disp * 4))),
+            0xcc..=0xcf => self.gbr.map(|gbr| format!("{} + r0", describe(gbr))),
+            _ => None,
+        }
+    }
+
+    // literal is the value a pc-relative mov.l loads
+    fn step(&mut self, op: u32, literal: Option<u32>) {
+        let n = ((op >> 8) & 0xf) as usize;
+        let m = ((op >> 4) & 0xf) as usize;
+        if op & 0xf000 == 0xd000 {
+            self.registers[n] = literal;
+        } else if op & 0xf000 == 0xe000 {
+            self.registers[n] = Some(op as u8 as i8 as u32);
+        } else if op & 0xf0ff == 0x401e {
+            self.gbr = self.registers[n];
+        } else if op & 0xf0ff == 0x4017 {
+            self.gbr = None;
+            self.registers[n] = None;
+        } else if op & 0xf0ff == 0x0012 {
+            self.registers[n] = self.gbr;
+        } else if op & 0xf00f == 0x6003 {
+            self.registers[n] = self.registers[m];
+        } else if op & 0xf0ff == 0x400b || op & 0xf000 == 0xb000 || op & 0xf0ff == 0x0003 {
+            // calls may change r0-r7
+            self.registers[..8].fill(None);
+        } else {
+            self.registers[n] = None;
+            self.registers[m] = None;
+            self.registers[0] = None;
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct Options {
     target_path: String,
@@ -1156,9 +1271,67 @@ struct Options {
     #[serde(default)]
     check_layout: bool,
     decomp_empty_funcs: bool,
+    #[serde(default, deserialize_with = "deserialize_yes_no")]
+    migrate_rodata_to_functions: bool,
+    #[serde(default)]
+    section_order: Vec<String>,
+    #[serde(default)]
+    sha1: Option<String>,
+    // Where inc_asm.h and macro.inc are generated, src_path by default
+    #[serde(default)]
+    include_path: Option<String>,
+    // Addresses of functions being decompiled; new C files get a
+    // NON_MATCHING skeleton for them
+    #[serde(default)]
+    non_matching: Vec<u32>,
+    // C compiler for the generated build.ninja, run with -S
+    #[serde(default = "default_cc")]
+    cc: String,
+    // Command build.ninja runs verify with, e.g.
+    // `cargo run --release --manifest-path rust-dis/Cargo.toml --`
+    #[serde(default = "default_splitter")]
+    splitter: String,
+    #[serde(default)]
+    naming: naming::Naming,
+    // Library signature files, see signatures.rs
+    #[serde(default)]
+    signatures: Vec<String>,
 }
 
-#[derive(Debug)]
+fn default_cc() -> String {
+    "sh2-gcc -O2 -m2 -fsigned-char".to_string()
+}
+
+fn default_splitter() -> String {
+    "rust-dis".to_string()
+}
+
+// splat configs spell booleans as yes/no, which YAML 1.2 reads as strings.
+fn deserialize_yes_no<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum YesNo {
+        Bool(bool),
+        Word(String),
+    }
+
+    match <YesNo as serde::Deserialize>::deserialize(deserializer)? {
+        YesNo::Bool(value) => Ok(value),
+        YesNo::Word(word) => match word.to_ascii_lowercase().as_str() {
+            "yes" | "true" | "on" => Ok(true),
+            "no" | "false" | "off" => Ok(false),
+            _ => Err(serde::de::Error::custom(format!(
+                "expected yes or no, found {}",
+                word
+            ))),
+        },
+    }
+}
+
+#[derive(Debug, Default)]
 struct Subsegment {
     start: u64,
     end: Option<u64>,
@@ -1166,6 +1339,8 @@ struct Subsegment {
     file: Option<String>,
     function_ranges: Vec<[u32; 2]>,
     data_ranges: Vec<[u32; 2]>,
+    // [start, end, value]: GBR on entry to the functions starting in the range
+    gbr: Vec<[u32; 3]>,
 }
 
 impl<'de> serde::Deserialize<'de> for Subsegment {
@@ -1186,6 +1361,8 @@ impl<'de> serde::Deserialize<'de> for Subsegment {
                 function_ranges: Vec<[u32; 2]>,
                 #[serde(default)]
                 data_ranges: Vec<[u32; 2]>,
+                #[serde(default)]
+                gbr: Vec<[u32; 3]>,
             },
             Compact((u64, String, String)),
         }
@@ -1198,6 +1375,7 @@ impl<'de> serde::Deserialize<'de> for Subsegment {
                 file,
                 function_ranges,
                 data_ranges,
+                gbr,
             } => Ok(Self {
                 start,
                 end,
@@ -1205,6 +1383,7 @@ impl<'de> serde::Deserialize<'de> for Subsegment {
                 file,
                 function_ranges,
                 data_ranges,
+                gbr,
             }),
             SubsegmentSyntax::Compact((start, segment_type, file)) => Ok(Self {
                 start,
@@ -1213,6 +1392,7 @@ impl<'de> serde::Deserialize<'de> for Subsegment {
                 file: Some(file),
                 function_ranges: Vec::new(),
                 data_ranges: Vec::new(),
+                gbr: Vec::new(),
             }),
         }
     }
@@ -1227,24 +1407,244 @@ struct Segment {
     subsegments: Option<Vec<Subsegment>>,
     vram: u64,
     subalign: Option<u64>,
+    #[serde(default)]
+    bss_size: Option<u64>,
+    #[serde(default)]
+    bss_vram: Option<u64>,
+    #[serde(default)]
+    sha1: Option<String>,
+}
+
+// End of the segment's bytes in the target. An odd end is inclusive. Without
+// an end on its last subsegment, a segment runs to the next one, and the last
+// segment to the end of the file.
+fn segment_end(segments: &[Segment], index: usize, file_len: u64) -> u64 {
+    let segment = &segments[index];
+    let next_start = segments
+        .iter()
+        .map(|other| other.start)
+        .filter(|&start| start > segment.start)
+        .min()
+        .unwrap_or(file_len);
+    let end = segment
+        .subsegments
+        .as_ref()
+        .and_then(|subsegments| subsegments.last())
+        .and_then(|subsegment| subsegment.end)
+        .unwrap_or(next_start);
+    end + (end & 1)
+}
+
+fn sha1_hex(bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+#[derive(Debug, PartialEq)]
+struct BssRange {
+    vram: u64,
+    size: u64,
+}
+
+impl BssRange {
+    fn contains(&self, addr: u32) -> bool {
+        (addr as u64) >= self.vram && (addr as u64) < self.vram + self.size
+    }
+}
+
+// Zero-initialised memory lives past the end of the loaded data unless the
+// segment places it explicitly with bss_vram.
+fn segment_bss(segments: &[Segment], index: usize, file_len: u64) -> Option<BssRange> {
+    let segment = &segments[index];
+    let size = segment.bss_size?;
+    let data_end = segment_end(segments, index, file_len);
+    Some(BssRange {
+        vram: segment.bss_vram.unwrap_or(segment.vram + data_end),
+        size,
+    })
+}
+
+// Name every literal pool value that points into the bss range. These are
+// merged into the user symbols so a hand-written name still wins.
+fn bss_literal_symbols(
+    file_contents: &[u8],
+    section_start: u64,
+    section_end: u64,
+    bss: &BssRange,
+) -> BTreeMap<u32, String> {
+    let mut symbols = BTreeMap::new();
+    for i in (section_start..section_end).step_by(2) {
+        let op = (file_contents[i as usize] as u32) << 8 | file_contents[i as usize + 1] as u32;
+        if op & 0xf000 != 0xd000 {
+            continue;
+        }
+        let target = ((i as u32 + 4 + (op & 0xff) * 4) & !3) as usize;
+        if target + 4 > section_end as usize {
+            continue;
+        }
+        let value = u32::from_be_bytes([
+            file_contents[target],
+            file_contents[target + 1],
+            file_contents[target + 2],
+            file_contents[target + 3],
+        ]);
+        if let Some(addr) = saturn::aliases(value)
+            .into_iter()
+            .find(|&addr| bss.contains(addr))
+        {
+            symbols.insert(addr, naming::symbol(&naming::bss(addr)));
+        }
+    }
+    symbols
 }
 
 #[derive(Debug, Deserialize)]
 struct Config {
     options: Options,
     segments: Option<Vec<Segment>>,
+    #[serde(skip)]
+    lines: YamlLines,
+    #[serde(skip)]
+    filename: String,
+    // loaded from options.signatures
+    #[serde(skip)]
+    signatures: Vec<signatures::Signature>,
+}
+
+// Line numbers of every key and sequence item in the config, addressed by
+// paths like `segments[0].subsegments[2].end`.
+#[derive(Debug, Default)]
+struct YamlLines(HashMap<String, usize>);
+
+impl YamlLines {
+    fn parse(contents: &str) -> YamlLines {
+        enum Frame {
+            Mapping { path: String, key: Option<String> },
+            Sequence { path: String, index: usize },
+        }
+
+        struct Recorder {
+            lines: HashMap<String, usize>,
+            stack: Vec<Frame>,
+        }
+
+        impl Recorder {
+            // Path of the value starting at `line`, or None if it is a mapping key.
+            fn value_path(&mut self, key: Option<&str>, line: usize) -> Option<String> {
+                match self.stack.last_mut() {
+                    None => Some(String::new()),
+                    Some(Frame::Mapping { path, key: pending }) => match pending.take() {
+                        Some(name) => Some(name),
+                        None => {
+                            let name = match path.is_empty() {
+                                true => key.unwrap_or_default().to_string(),
+                                false => format!("{}.{}", path, key.unwrap_or_default()),
+                            };
+                            self.lines.insert(name.clone(), line);
+                            *pending = Some(name);
+                            None
+                        }
+                    },
+                    Some(Frame::Sequence { path, index }) => {
+                        let name = format!("{}[{}]", path, index);
+                        *index += 1;
+                        self.lines.insert(name.clone(), line);
+                        Some(name)
+                    }
+                }
+            }
+        }
+
+        impl yaml_rust::parser::MarkedEventReceiver for Recorder {
+            fn on_event(&mut self, event: yaml_rust::Event, mark: yaml_rust::scanner::Marker) {
+                match event {
+                    yaml_rust::Event::Scalar(value, ..) => {
+                        self.value_path(Some(&value), mark.line());
+                    }
+                    yaml_rust::Event::MappingStart(_) => {
+                        if let Some(path) = self.value_path(None, mark.line()) {
+                            self.stack.push(Frame::Mapping { path, key: None });
+                        }
+                    }
+                    yaml_rust::Event::SequenceStart(_) => {
+                        if let Some(path) = self.value_path(None, mark.line()) {
+                            self.stack.push(Frame::Sequence { path, index: 0 });
+                        }
+                    }
+                    yaml_rust::Event::MappingEnd | yaml_rust::Event::SequenceEnd => {
+                        self.stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut recorder = Recorder {
+            lines: HashMap::new(),
+            stack: Vec::new(),
+        };
+        // serde_yaml has already reported any syntax error
+        let _ = yaml_rust::parser::Parser::new(contents.chars()).load(&mut recorder, false);
+        YamlLines(recorder.lines)
+    }
+
+    // The closest recorded line, so compact `[start, type, file]` subsegments
+    // still point at their own line.
+    fn line_of(&self, path: &str) -> Option<usize> {
+        let mut path = path;
+        loop {
+            if let Some(&line) = self.0.get(path) {
+                return Some(line);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ConfigProblem {
+    line: Option<usize>,
+    field: String,
+    message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Config {
+    fn problem(&self, field: String, message: String) -> ConfigProblem {
+        ConfigProblem {
+            line: self.lines.line_of(&field),
+            field,
+            message,
+        }
+    }
 }
 
-fn parse_yaml2(filename: String) -> Config {
+fn parse_yaml2(filename: String) -> Result<Config, ConfigProblem> {
     // Read the YAML configuration file
-    let mut file =
-        File::open(filename.clone()).expect(&format!("Failed to open the file {}", filename));
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .expect("Failed to read the file.");
+    let contents = std::fs::read_to_string(&filename).map_err(|error| ConfigProblem {
+        line: None,
+        field: String::new(),
+        message: format!("failed to read {}: {}", filename, error),
+    })?;
 
     // Parse the YAML into a Config struct
-    let mut config: Config = serde_yaml::from_str(&contents).expect("Failed to parse YAML.");
+    let mut config: Config = serde_yaml::from_str(&contents).map_err(|error| ConfigProblem {
+        line: error.location().map(|location| location.line()),
+        field: String::new(),
+        message: error.to_string(),
+    })?;
+    config.lines = YamlLines::parse(&contents);
+    config.filename = filename;
     if let Some(ref mut segments) = config.segments {
         for segment in segments {
             if let Some(ref mut subsegments) = segment.subsegments {
@@ -1256,7 +1656,296 @@ fn parse_yaml2(filename: String) -> Config {
             }
         }
     }
-    config
+    Ok(config)
+}
+
+// Check everything handle_segments relies on, so a bad config is reported up
+// front rather than as a panic or silently wrong output halfway through.
+fn validate_config(config: &Config, target: Option<&[u8]>) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+
+    if let (Some(expected), Some(target)) = (&config.options.sha1, target) {
+        let actual = sha1_hex(target);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            problems.push(config.problem(
+                "options.sha1".to_string(),
+                format!(
+                    "{} has SHA-1 {}, expected {}",
+                    config.options.target_path, actual, expected
+                ),
+            ));
+        }
+    }
+
+    let file_len = target.map_or(0, |target| target.len() as u64);
+    let segments = config.segments.as_deref().unwrap_or_default();
+    let highest_vram = (0..segments.len())
+        .map(|i| segments[i].vram + segment_end(segments, i, file_len))
+        .max()
+        .unwrap_or(0);
+    for (field, message) in naming::problems(&config.options.naming, highest_vram as u32) {
+        problems.push(config.problem(format!("options.naming.{}", field), message));
+    }
+    let dos_file_names = config.options.naming.dos_file_names;
+    let (_, signature_problems) = load_signatures(&config.options.signatures);
+    for (k, message) in signature_problems {
+        problems.push(config.problem(format!("options.signatures[{}]", k), message));
+    }
+
+    for (i, segment) in config.segments.iter().flatten().enumerate() {
+        let field = |name: &str| format!("segments[{}].{}", i, name);
+        if dos_file_names && !naming::is_dos_name(&segment.name) {
+            problems.push(config.problem(
+                field("name"),
+                format!("{} is not a DOS 8.3 file name", segment.name),
+            ));
+        }
+        if segment.vram > u32::MAX as u64 {
+            problems.push(config.problem(
+                field("vram"),
+                format!("0x{:X} is outside the SH-2 address space", segment.vram),
+            ));
+        } else if segment.vram & 3 != 0 {
+            problems.push(config.problem(
+                field("vram"),
+                format!("0x{:08X} is not 4-byte aligned", segment.vram),
+            ));
+        }
+        if let (Some(expected), Some(target)) = (&segment.sha1, target) {
+            let end = segment_end(segments, i, target.len() as u64);
+            if let Some(bytes) = target.get(segment.start as usize..end as usize) {
+                let actual = sha1_hex(bytes);
+                if !actual.eq_ignore_ascii_case(expected.trim()) {
+                    problems.push(config.problem(
+                        field("sha1"),
+                        format!(
+                            "bytes 0x{:X}-0x{:X} have SHA-1 {}, expected {}",
+                            segment.start, end, actual, expected
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let subsegments = segment.subsegments.as_deref().unwrap_or_default();
+        for (j, subsegment) in subsegments.iter().enumerate() {
+            let field = |name: &str| format!("segments[{}].subsegments[{}].{}", i, j, name);
+            let end = subsegment
+                .end
+                .or(target.map(|target| target.len() as u64))
+                .unwrap_or(subsegment.start);
+
+            if let Some(file) = subsegment.file.as_ref().filter(|_| dos_file_names) {
+                if !naming::is_dos_name(file.rsplit('/').next().unwrap_or(file)) {
+                    problems.push(config.problem(
+                        field("file"),
+                        format!("{} is not a DOS 8.3 file name", file),
+                    ));
+                }
+            }
+            if subsegment.start & 1 != 0 {
+                problems.push(config.problem(
+                    field("start"),
+                    format!("0x{:X} is not 2-byte aligned", subsegment.start),
+                ));
+            }
+            if end < subsegment.start {
+                problems.push(config.problem(
+                    field("end"),
+                    format!("0x{:X} is before start 0x{:X}", end, subsegment.start),
+                ));
+            }
+            if let Some(target) = target {
+                // an odd end is inclusive, its last word still has to be read
+                if end + (end & 1) > target.len() as u64 {
+                    problems.push(config.problem(
+                        field("end"),
+                        format!(
+                            "0x{:X} is beyond the end of {} (0x{:X} bytes)",
+                            end,
+                            config.options.target_path,
+                            target.len()
+                        ),
+                    ));
+                }
+            }
+            if let Some(next) = subsegments.get(j + 1) {
+                if next.start < subsegment.start {
+                    problems.push(config.problem(
+                        format!("segments[{}].subsegments[{}].start", i, j + 1),
+                        format!(
+                            "0x{:X} is before the previous subsegment at 0x{:X}",
+                            next.start, subsegment.start
+                        ),
+                    ));
+                } else if next.start < end {
+                    problems.push(config.problem(
+                        field("end"),
+                        format!(
+                            "0x{:X} overlaps the next subsegment at 0x{:X}",
+                            end, next.start
+                        ),
+                    ));
+                }
+            }
+
+            let gbr_ranges: Vec<[u32; 2]> = subsegment
+                .gbr
+                .iter()
+                .map(|&[start, end, _]| [start, end])
+                .collect();
+            let forced_ranges = [
+                ("function_ranges", &subsegment.function_ranges),
+                ("data_ranges", &subsegment.data_ranges),
+                ("gbr", &gbr_ranges),
+            ];
+            for (name, ranges) in forced_ranges {
+                for (k, &[range_start, range_end]) in ranges.iter().enumerate() {
+                    let field = field(&format!("{}[{}]", name, k));
+                    if name == "function_ranges" && range_start & 1 != 0 {
+                        problems.push(config.problem(
+                            field.clone(),
+                            format!("0x{:X} is not 2-byte aligned", range_start),
+                        ));
+                    }
+                    if range_end < range_start {
+                        problems.push(config.problem(
+                            field,
+                            format!("0x{:X} is before 0x{:X}", range_end, range_start),
+                        ));
+                    } else if (range_start as u64) < subsegment.start || range_end as u64 >= end {
+                        problems.push(config.problem(
+                            field,
+                            format!(
+                                "[0x{:X}, 0x{:X}] is outside the subsegment 0x{:X}-0x{:X}",
+                                range_start, range_end, subsegment.start, end
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // the linker places inputs in section order, which has to agree with
+        // where the subsegments are
+        let mut inputs = linker_inputs(segment);
+        let section_order = &config.options.section_order;
+        order_linker_inputs(&mut inputs, section_order);
+        let out_of_order = inputs
+            .windows(2)
+            .find(|pair| pair[1].start < pair[0].start)
+            .filter(|_| !section_order.is_empty());
+        if let Some(pair) = out_of_order {
+            problems.push(config.problem(
+                format!(
+                    "options.section_order[{}]",
+                    section_rank(&pair[0].section, section_order)
+                ),
+                format!(
+                    "{} links {} {} at 0x{:X} before {} {} at 0x{:X}",
+                    segment.name,
+                    pair[0].object,
+                    pair[0].section,
+                    pair[0].start,
+                    pair[1].object,
+                    pair[1].section,
+                    pair[1].start
+                ),
+            ));
+        }
+    }
+
+    problems
+}
+
+// Every signature in the files, and (index, problem) for files that cannot be
+// read or parsed.
+fn load_signatures(paths: &[String]) -> (Vec<signatures::Signature>, Vec<(usize, String)>) {
+    let mut loaded = Vec::new();
+    let mut problems = Vec::new();
+    for (k, path) in paths.iter().enumerate() {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => match signatures::parse(&contents) {
+                Ok(signatures) => loaded.extend(signatures),
+                Err((line, message)) => {
+                    problems.push((k, format!("{}:{}: {}", path, line, message)))
+                }
+            },
+            Err(err) => problems.push((k, format!("{}: {}", path, err))),
+        }
+    }
+    (loaded, problems)
+}
+
+// Compare a rebuilt image with the configured hashes. Segments without their
+// own sha1 are compared against the original target. Returns the first
+// difference found.
+fn check_build(config: &Config, target: Option<&[u8]>, built: &[u8]) -> Option<String> {
+    if let Some(expected) = &config.options.sha1 {
+        if sha1_hex(built).eq_ignore_ascii_case(expected.trim()) {
+            return None;
+        }
+    }
+
+    let segments = config.segments.as_deref().unwrap_or_default();
+    for (i, segment) in segments.iter().enumerate() {
+        let end = segment_end(
+            segments,
+            i,
+            target.map_or(built.len(), |target| target.len()) as u64,
+        );
+        let range = segment.start as usize..end as usize;
+        let Some(bytes) = built.get(range.clone()) else {
+            return Some(format!(
+                "segment {} (0x{:X}-0x{:X}) is missing, the build is only 0x{:X} bytes",
+                segment.name,
+                segment.start,
+                end,
+                built.len()
+            ));
+        };
+        let expected = match (&segment.sha1, target.and_then(|target| target.get(range))) {
+            (Some(expected), _) => expected.trim().to_ascii_lowercase(),
+            (None, Some(original)) => sha1_hex(original),
+            (None, None) => continue,
+        };
+        let actual = sha1_hex(bytes);
+        if actual != expected {
+            let mut message = format!(
+                "segment {} (0x{:X}-0x{:X}) differs: SHA-1 {}, expected {}",
+                segment.name, segment.start, end, actual, expected
+            );
+            let original = target.and_then(|target| target.get(segment.start as usize..));
+            if let Some(offset) = original.and_then(|original| {
+                bytes
+                    .iter()
+                    .zip(original)
+                    .position(|(built, original)| built != original)
+            }) {
+                message.push_str(&format!(
+                    ", first difference at 0x{:X}",
+                    segment.start as usize + offset
+                ));
+            }
+            return Some(message);
+        }
+    }
+
+    match &config.options.sha1 {
+        Some(expected) => Some(format!(
+            "build has SHA-1 {}, expected {}",
+            sha1_hex(built),
+            expected
+        )),
+        None => match target {
+            Some(target) if target.len() != built.len() => Some(format!(
+                "build is 0x{:X} bytes, expected 0x{:X}",
+                built.len(),
+                target.len()
+            )),
+            _ => None,
+        },
+    }
 }
 
 fn read_user_symbols(filename: &str) -> HashMap<u32, String> {
@@ -1264,7 +1953,8 @@ fn read_user_symbols(filename: &str) -> HashMap<u32, String> {
         return HashMap::new();
     };
     let pattern =
-        Regex::new(r"^\s*([A-Za-z_][A-Za-z0-9_]*)\s*=\s*(0x[0-9A-Fa-f]+)\s*;\s*$").unwrap();
+        Regex::new(r"^\s*([A-Za-z_][A-Za-z0-9_]*)\s*=\s*(0x[0-9A-Fa-f]+)\s*;\s*(?://.*)?$")
+            .unwrap();
     let mut symbols = HashMap::new();
     for line in contents.lines() {
         let Some(captures) = pattern.captures(line) else {
@@ -1281,13 +1971,69 @@ fn read_user_symbols(filename: &str) -> HashMap<u32, String> {
     symbols
 }
 
+// A user symbol can carry its C prototype in a trailing comment:
+// `_RunMainEngine = 0x06070A60; // void RunMainEngine(s32 mode);`
+fn read_user_prototypes(filename: &str) -> HashMap<u32, String> {
+    let Ok(contents) = std::fs::read_to_string(filename) else {
+        return HashMap::new();
+    };
+    let pattern = Regex::new(
+        r"^\s*[A-Za-z_][A-Za-z0-9_]*\s*=\s*0x([0-9A-Fa-f]+)\s*;\s*//\s*([^(]+\(.*\))\s*;?\s*$",
+    )
+    .unwrap();
+    let mut prototypes = HashMap::new();
+    for line in contents.lines() {
+        if let Some(captures) = pattern.captures(line) {
+            let address = u32::from_str_radix(&captures[1], 16).unwrap();
+            prototypes.insert(address, format!("{};", &captures[2]));
+        }
+    }
+    prototypes
+}
+
+// The address and name of the symbol at value, or at an address aliasing it
+// through another SH-2 cache area. User symbols take priority over the
+// Saturn register map.
+fn resolve_literal(value: u32, user_symbols: &HashMap<u32, String>) -> Option<(u32, String)> {
+    saturn::aliases(value).into_iter().find_map(|addr| {
+        user_symbols
+            .get(&addr)
+            .cloned()
+            .or_else(|| saturn::register_name(addr).map(str::to_string))
+            .map(|name| (addr, name))
+    })
+}
+
+// An aliased address keeps its area as an offset, e.g. `D_06070000 + 0x20000000`,
+// so it relinks wherever the symbol moves.
+fn literal_symbol(value: u32, user_symbols: &HashMap<u32, String>) -> Option<String> {
+    let (addr, name) = resolve_literal(value, user_symbols)?;
+    Some(match value.cmp(&addr) {
+        std::cmp::Ordering::Equal => name,
+        std::cmp::Ordering::Greater => format!("{} + 0x{:08X}", name, value - addr),
+        std::cmp::Ordering::Less => format!("{} - 0x{:08X}", name, addr - value),
+    })
+}
+
 fn format_literal(value: u32, user_symbols: &HashMap<u32, String>, _allow_symbol: bool) -> String {
-    if let Some(symbol) = user_symbols.get(&value) {
-        return symbol.clone();
+    literal_symbol(value, user_symbols).unwrap_or_else(|| format!("0x{:08X}", value))
+}
+
+// The value a `mov.l @(disp,pc),rN` at v_addr loads, if it is in the section.
+fn mov_l_literal(
+    file_contents: &[u8],
+    v_addr: u32,
+    op: u32,
+    virtual_base_addr: u64,
+) -> Option<u32> {
+    if op & 0xf000 != 0xd000 {
+        return None;
     }
-    format!("0x{:08X}", value)
+    let target = ((op & 0xff) * 4 + 4 + v_addr) & 0xfffffffc;
+    let phys = target.wrapping_sub(virtual_base_addr as u32) as usize;
+    let bytes = file_contents.get(phys..phys + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
 }
-use std::io::Write;
 
 fn emit_c_file(functions: &BTreeMap<u32, DisassembledFunc>, output_path: String) {
     let filename = format!("{}/output.c", output_path);
@@ -1307,13 +2053,7 @@ fn emit_c_file(functions: &BTreeMap<u32, DisassembledFunc>, output_path: String)
     }
 }
 
-fn emit_asm_file(filename: String, data: String) {
-    let mut file = std::fs::File::create(filename).expect("Failed to create file.");
-    writeln!(&mut file, "{}", data).expect("Failed to write to file.");
-}
-
-use std::fmt;
-
+#[derive(Default)]
 struct DisassembledFunc {
     addr: u32,
     end: u32,
@@ -1321,6 +2061,12 @@ struct DisassembledFunc {
     data: bool,
     name: String,
     file: String,
+    // .long literal pool values, used to attribute rodata to its user
+    references: Vec<u32>,
+    // BIOS vectors this function calls through
+    bios_calls: Vec<u32>,
+    // the library signature that matched it
+    library: Option<signatures::Signature>,
 }
 
 fn check_data_labels(
@@ -1360,6 +2106,7 @@ fn check_data_labels(
                         virtual_addr,
                         format_literal(data, user_symbols, !value.is_function)
                     ));
+                    func.references.push(data);
                 }
 
                 // skip next instruction since we used it
@@ -1370,20 +2117,18 @@ fn check_data_labels(
     }
 }
 
-fn handle_code_section(
+// The functions find_funcs sees in a code section, with the subsegment's
+// function_ranges taking the place of any they overlap.
+fn section_function_ranges(
     file_contents: &Vec<u8>,
     section_start: u64,
     section_end: u64,
-    virtual_base_addr: u64,
-    user_symbols: &HashMap<u32, String>,
-    forced_function_ranges: &[[u32; 2]],
-    forced_data_ranges: &[[u32; 2]],
-) -> (BTreeMap<u32, DisassembledFunc>) {
-    let len = file_contents.len();
+    hints: &Subsegment,
+) -> Vec<FunctionRange> {
     let mut ranges = Vec::<FunctionRange>::new();
-    find_funcs(&file_contents, section_start, section_end, &mut ranges);
+    find_funcs(file_contents, section_start, section_end, &mut ranges);
 
-    for &[phys_start, phys_end] in forced_function_ranges {
+    for &[phys_start, phys_end] in &hints.function_ranges {
         assert!(phys_start >= section_start as u32 && phys_end < section_end as u32);
         ranges.retain(|range| range.phys_end < phys_start || range.phys_start > phys_end);
         ranges.push(FunctionRange {
@@ -1393,6 +2138,20 @@ fn handle_code_section(
         });
     }
     ranges.sort_by_key(|range| range.phys_start);
+    ranges
+}
+
+fn handle_code_section(
+    file_contents: &Vec<u8>,
+    section_start: u64,
+    section_end: u64,
+    virtual_base_addr: u64,
+    user_symbols: &HashMap<u32, String>,
+    hints: &Subsegment,
+) -> (BTreeMap<u32, DisassembledFunc>) {
+    let forced_data_ranges = &hints.data_ranges;
+    let len = file_contents.len();
+    let ranges = section_function_ranges(file_contents, section_start, section_end, hints);
 
     for &[phys_start, phys_end] in forced_data_ranges {
         assert!(phys_start >= section_start as u32 && phys_end < section_end as u32);
@@ -1406,7 +2165,14 @@ fn handle_code_section(
     };
 
     if ranges.len() == 0 {
-        println!("no ranges");
+        diagnose(
+            "no-functions",
+            (section_start + virtual_base_addr) as u32,
+            format!(
+                "no functions found in code 0x{:X}-0x{:X}",
+                section_start, section_end
+            ),
+        );
     }
 
     let mut data_labels = HashMap::<u32, DataLabel>::new();
@@ -1450,32 +2216,73 @@ fn handle_code_section(
 
     remove_jump_table_internal_labels(&mut branch_labels, &jump_table_entries);
 
+    for (&addr, label) in &data_labels {
+        let (is_in_func, start_address) =
+            infunc(addr.wrapping_sub(virtual_base_addr as u32), &ranges);
+        if is_in_func {
+            diagnose(
+                "data-in-function",
+                addr,
+                format!(
+                    "literal pool entry loaded from 0x{:08X} is inside the body of {}",
+                    label.source,
+                    naming::function(start_address + virtual_base_addr as u32)
+                ),
+            );
+        }
+    }
+
     for label in data_labels.values_mut() {
         if label.size == 4 {
             label.is_function = literal_feeds_call(file_contents, label.source, virtual_base_addr);
         }
     }
-    let mut disassembled_funcs = BTreeMap::<u32, DisassembledFunc>::new();
 
-    // create emtpy ones for all funcs
-    for f in &ranges {
-        let virtual_addr =
-            TryInto::<u32>::try_into(f.phys_start).unwrap() + virtual_base_addr as u32;
-        disassembled_funcs
+    // name BIOS calls, a user symbol for the vector still wins
+    let mut symbols = user_symbols.clone();
+    let mut bios_calls = BTreeMap::<u32, u32>::new();
+    for (&addr, label) in &data_labels {
+        let offset = addr.wrapping_sub(virtual_base_addr as u32) as usize;
+        let Some(bytes) = file_contents.get(offset..offset + 4) else {
+            continue;
+        };
+        let vector = u32::from_be_bytes(bytes.try_into().unwrap());
+        let Some(name) = saturn::bios_call_name(vector) else {
+            continue;
+        };
+        if label.size == 4 {
+            if let Some(jsr) = bios_call(file_contents, label.source, virtual_base_addr) {
+                symbols.entry(vector).or_insert_with(|| name.to_string());
+                bios_calls.insert(jsr, vector);
+            }
+        }
+    }
+    let user_symbols = &symbols;
+    let mut disassembled_funcs = BTreeMap::<u32, DisassembledFunc>::new();
+
+    // create emtpy ones for all funcs
+    for f in &ranges {
+        let virtual_addr =
+            TryInto::<u32>::try_into(f.phys_start).unwrap() + virtual_base_addr as u32;
+        disassembled_funcs
             .entry(f.phys_start)
             .or_insert(DisassembledFunc {
                 addr: f.phys_start,
                 end: f.phys_end,
                 text: "".to_string(),
                 data: f.is_data,
-                name: format!("f{:07X}", virtual_addr),
+                name: naming::function_file(virtual_addr),
                 file: "_".to_string(),
+                references: Vec::new(),
+                bios_calls: Vec::new(),
+                library: None,
             });
     }
 
     let mut monolithic = String::new();
 
     let mut skip_next = false;
+    let mut gbr = GbrTracker::default();
 
     for i in (section_start..section_end).step_by(2) {
         let ii = i as usize;
@@ -1494,8 +2301,15 @@ fn handle_code_section(
         if i as u32 == start_address {
             if let Some(func) = disassembled_funcs.get_mut(&(start_address as u32)) {
                 func.text
-                    .push_str(&format!("glabel func_{:08X}\n", virtual_addr));
+                    .push_str(&format!("glabel {}\n", naming::function(virtual_addr)));
             }
+            gbr.enter_function(
+                hints
+                    .gbr
+                    .iter()
+                    .find(|&&[start, end, _]| start_address >= start && start_address <= end)
+                    .map(|&[_, _, value]| value),
+            );
         }
 
         // check to emit data, use extended addr
@@ -1505,13 +2319,14 @@ fn handle_code_section(
                     "/* 0x{:08X} */ .word 0x{:04X}\n",
                     virtual_addr, instr
                 ));
-                println!("\ti is {:08X} func.data continue", i);
+                log::trace!("\ti is {:08X} func.data continue", i);
 
                 continue;
             }
         }
 
         if branch_labels.contains_key(&virtual_addr.try_into().unwrap()) {
+            gbr.enter_label();
             if let Some(value) = branch_labels.get(&virtual_addr.try_into().unwrap()) {
                 // Use the label
                 if let Some(func) = disassembled_funcs.get_mut(&(start_address as u32)) {
@@ -1571,6 +2386,31 @@ fn handle_code_section(
                 &mut data_labels,
                 &mut branch_labels,
             );
+            if string.ends_with("/* unknown instruction */") {
+                diagnose(
+                    "unknown-instruction",
+                    virtual_addr,
+                    format!(
+                        "0x{:04X} in {} is not an SH-2 instruction",
+                        instr,
+                        naming::function(start_address + virtual_base_addr as u32)
+                    ),
+                );
+            }
+            let literal = mov_l_literal(file_contents, virtual_addr, instr, virtual_base_addr);
+            if let Some(symbol) = literal.and_then(|value| literal_symbol(value, user_symbols)) {
+                string.push_str(&format!(" /* {} */", symbol));
+            }
+            if let Some(comment) = gbr.comment(instr, user_symbols) {
+                string.push_str(&format!(" /* {} */", comment));
+            }
+            gbr.step(instr, literal);
+            if let Some(vector) = bios_calls.get(&virtual_addr) {
+                string.push_str(&format!(" /* BIOS {} */", user_symbols[vector]));
+                if let Some(func) = disassembled_funcs.get_mut(&start_address) {
+                    func.bios_calls.push(*vector);
+                }
+            }
             if let Some(func) = disassembled_funcs.get_mut(&(start_address as u32)) {
                 func.text.push_str(&format!(
                     "/* 0x{:08X} 0x{:04X} */ {}\n",
@@ -1594,85 +2434,496 @@ fn handle_code_section(
 #[derive(Default)]
 struct ProcessedSection {
     is_code: bool,
+    section_type: String,
+    segment: String,
+    file: String,
+    // data symbols defined by `data`
+    labels: Vec<DataSymbol>,
     disassembled_funcs: BTreeMap<u32, DisassembledFunc>,
     data: String,
     addr: u64,
+    end: u64,
     vaddr: u64,
     vbase: u64,
 }
 
+fn emit_data_words(file_contents: &[u8], start: u64, end: u64, vram: u64) -> String {
+    let mut data_str = String::new();
+    for i in (start..end).step_by(2) {
+        let ii = i as usize;
+        let data: u32 = ((file_contents[ii] as u32) << 8) | file_contents[ii + 1] as u32;
+        data_str.push_str(&format!("/* 0x{:08X} */ .word 0x{:04X}\n", i + vram, data));
+    }
+    data_str
+}
+
+#[derive(Debug, PartialEq)]
+struct DataSymbol {
+    vram: u32,
+    size: u32,
+    name: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct RodataChunk {
+    start: u32,
+    end: u32,
+    owner: Option<u32>,
+}
+
+// Split a rodata range at every 2-aligned address a function loads from its
+// literal pool, as chunks are emitted in words. A chunk belongs to a function
+// when its first address is referenced and that function is the only one
+// referencing anything in it; everything else stays in the rodata subsegment.
+fn partition_rodata(
+    start: u32,
+    end: u32,
+    references: &BTreeMap<u32, BTreeSet<u32>>,
+) -> Vec<RodataChunk> {
+    let mut boundaries = vec![start];
+    boundaries.extend(
+        references
+            .range(start + 1..end)
+            .map(|(&addr, _)| addr)
+            .filter(|addr| addr & 1 == 0),
+    );
+    boundaries.push(end);
+
+    boundaries
+        .windows(2)
+        .map(|pair| {
+            let users: BTreeSet<u32> = references
+                .range(pair[0]..pair[1])
+                .flat_map(|(_, users)| users.iter().copied())
+                .collect();
+            let owner = match references.get(&pair[0]) {
+                Some(_) if users.len() == 1 => users.first().copied(),
+                _ => None,
+            };
+            RodataChunk {
+                start: pair[0],
+                end: pair[1],
+                owner,
+            }
+        })
+        .collect()
+}
+
+// A function's rodata is emitted with it, before or after the file's
+// INCLUDE_RODATA. Only a run at the start of the rodata owned, in address
+// order, by the functions just before it, or a run at the end owned by those
+// just after it, lands back at the same addresses. Other chunks lose their owner.
+fn keep_rodata_layout(
+    chunks: &mut [RodataChunk],
+    before: impl Fn(u32) -> bool,
+    after: impl Fn(u32) -> bool,
+) {
+    let mut prefix = 0;
+    let mut last = 0;
+    while let Some(owner) = chunks.get(prefix).and_then(|chunk| chunk.owner) {
+        if !before(owner) || owner < last {
+            break;
+        }
+        last = owner;
+        prefix += 1;
+    }
+    let mut suffix = chunks.len();
+    let mut next = u32::MAX;
+    while suffix > prefix {
+        match chunks[suffix - 1].owner {
+            Some(owner) if after(owner) && owner <= next => {
+                next = owner;
+                suffix -= 1;
+            }
+            _ => break,
+        }
+    }
+    for chunk in &mut chunks[prefix..suffix] {
+        chunk.owner = None;
+    }
+}
+
+fn is_rodata_section(section_type: &str) -> bool {
+    matches!(section_type, ".rodata" | "rodata")
+}
+
+fn migrate_rodata_to_functions(file_contents: &[u8], processed_sections: &mut [ProcessedSection]) {
+    let mut references = BTreeMap::<u32, BTreeSet<u32>>::new();
+    let mut function_files = HashMap::<u32, String>::new();
+    for section in processed_sections.iter().filter(|section| section.is_code) {
+        for func in section.disassembled_funcs.values() {
+            function_files.insert(func.addr + section.vbase as u32, section.file.clone());
+            for alias in func
+                .references
+                .iter()
+                .flat_map(|&value| saturn::aliases(value))
+            {
+                references
+                    .entry(alias)
+                    .or_default()
+                    .insert(func.addr + section.vbase as u32);
+            }
+        }
+    }
+
+    // each file's rodata ranges, to tell which functions come just before one
+    let mut rodata_ranges = Vec::<(String, u32, u32)>::new();
+    for section in processed_sections.iter() {
+        if !section.is_code && is_rodata_section(&section.section_type) {
+            let end = (section.end + section.vbase) as u32;
+            rodata_ranges.push((section.file.clone(), section.vaddr as u32, end));
+        }
+    }
+
+    let mut migrated = BTreeMap::<u32, String>::new();
+    for section in processed_sections.iter_mut() {
+        if section.is_code || !is_rodata_section(&section.section_type) {
+            continue;
+        }
+        let (start, end) = (section.vaddr as u32, (section.end + section.vbase) as u32);
+        let file_ranges: Vec<(u32, u32)> = rodata_ranges
+            .iter()
+            .filter(|(file, _, _)| *file == section.file)
+            .map(|&(_, range_start, range_end)| (range_start, range_end))
+            .collect();
+        let previous_end = file_ranges
+            .iter()
+            .map(|range| range.1)
+            .filter(|&range_end| range_end <= start)
+            .max()
+            .unwrap_or(0);
+        let next_start = file_ranges
+            .iter()
+            .map(|range| range.0)
+            .filter(|&range_start| range_start >= end)
+            .min()
+            .unwrap_or(u32::MAX);
+        let in_file = |owner: u32| function_files.get(&owner) == Some(&section.file);
+        let mut chunks = partition_rodata(start, end, &references);
+        keep_rodata_layout(
+            &mut chunks,
+            |owner| in_file(owner) && (previous_end..start).contains(&owner),
+            |owner| in_file(owner) && (end..next_start).contains(&owner),
+        );
+        section.data.clear();
+        section.labels.clear();
+        for chunk in chunks {
+            let words = emit_data_words(
+                file_contents,
+                (chunk.start - section.vbase as u32) as u64,
+                (chunk.end - section.vbase as u32) as u64,
+                section.vbase,
+            );
+            // every chunk is loaded from somewhere, so each gets a symbol
+            let name = if chunk.start as u64 == section.vaddr && chunk.owner.is_none() {
+                naming::data(chunk.start)
+            } else {
+                naming::rodata(chunk.start)
+            };
+            let text = match chunk.owner {
+                Some(owner) => migrated.entry(owner).or_default(),
+                None => &mut section.data,
+            };
+            text.push_str(&format!("glabel {}\n", name));
+            text.push_str(&words);
+            section.labels.push(DataSymbol {
+                vram: chunk.start,
+                size: chunk.end - chunk.start,
+                name,
+            });
+        }
+    }
+
+    for section in processed_sections
+        .iter_mut()
+        .filter(|section| section.is_code)
+    {
+        for func in section.disassembled_funcs.values_mut() {
+            if let Some(rodata) = migrated.get(&(func.addr + section.vbase as u32)) {
+                func.text.push_str(".section .rodata\n");
+                func.text.push_str(rodata);
+                func.text.push_str(".text\n");
+            }
+        }
+    }
+}
+
 fn write_c_file(
+    plan: &mut OutputPlan,
     config: &Config,
     path: &str,
     segment_name: &str,
     asm_path: &str,
     processed_sections: &Vec<ProcessedSection>,
-) {
+) -> Result<(), Error> {
     let filename = format!("{}/{}.c", path, segment_name);
-    let mut file = std::fs::File::create(filename).expect("Failed to create file.");
-    writeln!(&mut file, "#include \"inc_asm.h\"").expect("Failed to write to file.");
+    let mut file = "#include \"inc_asm.h\"\n".to_string();
 
     for processed_section in processed_sections {
         if !processed_section.is_code {
-            let name = format!("d{:07X}", processed_section.vaddr);
-            writeln!(
-                &mut file,
-                "INCLUDE_ASM(\"{}\", {}, d_{:08X});",
+            let name = naming::data_file(processed_section.vaddr as u32);
+            if is_rodata_section(&processed_section.section_type) {
+                file.push_str(&format!("INCLUDE_RODATA(\"{}\", {});\n", asm_path, name));
+                continue;
+            }
+            file.push_str(&format!(
+                "INCLUDE_ASM(\"{}\", {}, {});\n",
                 asm_path, // TODO fix hardcode
                 name,
-                processed_section.vaddr
-            )
-            .expect("Failed to write to file.");
+                naming::data(processed_section.vaddr as u32)
+            ));
         } else {
             for pair in &processed_section.disassembled_funcs {
                 // assume this is a empty function if the size is 8
+                let addr = pair.1.addr + processed_section.vbase as u32;
                 if (pair.1.end - pair.1.addr == 8) && config.options.decomp_empty_funcs {
-                    writeln!(&mut file, "void {}() {{}}", pair.1.name)
-                        .expect("Failed to write to file.");
+                    file.push_str(&format!("void {}() {{}}\n", naming::function(addr)));
                 } else {
-                    writeln!(
-                        &mut file,
-                        "INCLUDE_ASM(\"{}\", {}, func_{:08X});",
+                    let include = format!(
+                        "INCLUDE_ASM(\"{}\", {}, {});\n",
                         asm_path,
                         pair.1.name,
-                        pair.1.addr + processed_section.vbase as u32
-                    )
-                    .expect("Failed to write to file.");
+                        naming::function(addr)
+                    );
+                    if config.options.non_matching.contains(&addr) {
+                        file.push_str(&format!(
+                            "#ifndef NON_MATCHING\n{}#else\nvoid {}(void) {{\n}}\n#endif\n",
+                            include,
+                            naming::function(addr)
+                        ));
+                    } else {
+                        file.push_str(&include);
+                    }
+                }
+            }
+        }
+    }
+    plan.write(&filename, &file)
+}
+
+// Asm ids and function names pulled in by INCLUDE_ASM. A non-matching
+// function has C guarded by NON_MATCHING with the asm as the fallback.
+#[derive(Debug, Default)]
+struct CIncludes {
+    asm_only: HashSet<String>,
+    non_matching: HashSet<String>,
+}
+
+impl CIncludes {
+    fn contains(&self, name: &str) -> bool {
+        self.asm_only.contains(name) || self.non_matching.contains(name)
+    }
+
+    fn extend(&mut self, other: CIncludes) {
+        self.asm_only.extend(other.asm_only);
+        self.non_matching.extend(other.non_matching);
+    }
+}
+
+// Blank out comments and the contents of string and character literals,
+// keeping newlines, and join backslash-continued lines.
+fn strip_c_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                stripped.push(c);
+                while let Some(d) = chars.next() {
+                    if d == c || d == '\n' {
+                        stripped.push(d);
+                        break;
+                    }
+                    stripped.push(' ');
+                    if d == '\\' && chars.next_if(|&e| e != '\n').is_some() {
+                        stripped.push(' ');
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&d| d != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                stripped.push(' ');
+                let mut prev = ' ';
+                for d in chars.by_ref() {
+                    if d == '\n' {
+                        stripped.push('\n');
+                    }
+                    if prev == '*' && d == '/' {
+                        break;
+                    }
+                    prev = d;
                 }
             }
+            _ => stripped.push(c),
         }
     }
+    stripped.replace("\\\n", "")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Branch {
+    Taken,
+    NotTaken,
+    Unknown,
+    NonMatching,
+}
+
+fn condition_branch(condition: &str) -> Branch {
+    match condition.trim() {
+        _ if condition.contains("NON_MATCHING") => Branch::NonMatching,
+        "0" => Branch::NotTaken,
+        "1" => Branch::Taken,
+        _ => Branch::Unknown,
+    }
 }
 
-fn find_include_asm_in_c_file(filename: &str) -> io::Result<HashSet<String>> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let re = Regex::new(r#"INCLUDE_ASM(?:_NO_ALIGN)?\("(.*?)", (.*?), (.*?)\)"#).unwrap();
-    let mut result = HashSet::new();
+fn scan_c_source(source: &str) -> CIncludes {
+    let re = Regex::new(r#"INCLUDE_ASM(?:_NO_ALIGN)?\s*\(\s*"[^"]*"\s*,\s*(\w+)\s*,\s*(\w+)\s*\)"#)
+        .unwrap();
+    let mut result = CIncludes::default();
+    let mut stack = Vec::<Branch>::new();
+    let mut pending = String::new();
+
+    // Text between two directives shares their conditions, and an
+    // INCLUDE_ASM can span lines within it.
+    let mut flush = |pending: &mut String, stack: &[Branch]| {
+        if !stack.contains(&Branch::NotTaken) {
+            let set = if stack.contains(&Branch::NonMatching) {
+                &mut result.non_matching
+            } else {
+                &mut result.asm_only
+            };
+            for caps in re.captures_iter(pending) {
+                set.insert(caps[1].to_string());
+                set.insert(caps[2].to_string());
+            }
+        }
+        pending.clear();
+    };
 
-    for res in reader.lines() {
-        if let Some(caps) = re.captures(&res.unwrap().to_string()) {
-            let asm_id = caps.get(2).unwrap().as_str().trim().to_string();
-            let func_name = caps.get(3).unwrap().as_str().to_string();
-            result.insert(asm_id);
-            result.insert(func_name);
+    for line in strip_c_comments(source).lines() {
+        let Some(directive) = line.trim_start().strip_prefix('#') else {
+            pending.push_str(line);
+            pending.push('\n');
+            continue;
+        };
+        flush(&mut pending, &stack);
+        let directive = directive.trim_start();
+        let keyword_end = directive
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(directive.len());
+        let (keyword, condition) = directive.split_at(keyword_end);
+        match keyword {
+            "if" | "ifdef" | "ifndef" => stack.push(condition_branch(condition)),
+            "elif" => {
+                if let Some(top) = stack.last_mut() {
+                    *top = match *top {
+                        Branch::Taken => Branch::NotTaken,
+                        Branch::NotTaken => condition_branch(condition),
+                        other => other,
+                    };
+                }
+            }
+            "else" => {
+                if let Some(top) = stack.last_mut() {
+                    *top = match *top {
+                        Branch::Taken => Branch::NotTaken,
+                        Branch::NotTaken => Branch::Taken,
+                        other => other,
+                    };
+                }
+            }
+            "endif" => {
+                stack.pop();
+            }
+            _ => {}
         }
     }
+    flush(&mut pending, &stack);
+    result
+}
+
+// split writes these next to the C files so they always match what it emits.
+const INC_ASM_H: &str = include_str!("../output/inc_asm.h");
+const MACRO_INC: &str = include_str!("../output/macro.inc");
 
-    Ok(result)
+fn find_include_asm_in_c_file(filename: &str) -> io::Result<CIncludes> {
+    Ok(scan_c_source(&std::fs::read_to_string(filename)?))
 }
 
-fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
+// Disassemble every subsegment. Also returns the symbols for literals that
+// point into a segment's bss.
+fn process_segments(
+    file_contents: &Vec<u8>,
+    config: &Config,
+) -> (Vec<ProcessedSection>, BTreeMap<u32, String>) {
     let mut processed_sections = Vec::<ProcessedSection>::new();
+    let mut bss_symbols = BTreeMap::<u32, String>::new();
     if let Some(segments) = &config.segments {
-        for segment in segments {
-            let user_symbols = read_user_symbols(&format!(
+        for (index, segment) in segments.iter().enumerate() {
+            let mut user_symbols = read_user_symbols(&format!(
                 "{}/{}_user_syms.txt",
                 config.options.syms_path, segment.name
             ));
-            println!("Segment Name: {}", segment.name);
-            println!("Segment Type: {}", segment.segment_type);
-            println!("Segment Start: {}", segment.start);
+            if let Some(bss) = segment_bss(segments, index, file_contents.len() as u64) {
+                for subsegment in segment.subsegments.iter().flatten() {
+                    let subsegment_type = subsegment.segment_type.as_deref().unwrap_or("");
+                    if subsegment_type == "data" || subsegment_type.starts_with('.') {
+                        continue;
+                    }
+                    let end = subsegment.end.unwrap_or(file_contents.len() as u64);
+                    for (addr, name) in
+                        bss_literal_symbols(file_contents, subsegment.start, end, &bss)
+                    {
+                        user_symbols.entry(addr).or_insert_with(|| name.clone());
+                        bss_symbols.insert(addr, name);
+                    }
+                }
+            }
+            // name library functions, a hand-written name still wins
+            let mut library = BTreeMap::<usize, signatures::Signature>::new();
+            for subsegment in segment.subsegments.iter().flatten() {
+                let subsegment_type = subsegment.segment_type.as_deref().unwrap_or("");
+                if subsegment_type == "data" || subsegment_type.starts_with('.') {
+                    continue;
+                }
+                let end = subsegment.end.unwrap_or(file_contents.len() as u64);
+                let starts: Vec<usize> =
+                    section_function_ranges(file_contents, subsegment.start, end, subsegment)
+                        .iter()
+                        .filter(|range| !range.is_data)
+                        .map(|range| range.phys_start as usize)
+                        .collect();
+                for (offset, signature) in
+                    signatures::scan(&config.signatures, file_contents, &starts, end as usize)
+                {
+                    library.insert(offset, signature.clone());
+                }
+            }
+            // a function found more than once gets its address in each name
+            let mut matched = HashMap::<String, usize>::new();
+            for signature in library.values() {
+                *matched.entry(signature.name.clone()).or_default() += 1;
+            }
+            for (&offset, signature) in library.iter_mut() {
+                let vram = offset as u32 + segment.vram as u32;
+                if matched[&signature.name] > 1 {
+                    signature.name = format!("{}_{:08X}", signature.name, vram);
+                }
+                log::info!(
+                    "{} is {} from {}",
+                    naming::function(vram),
+                    signature.name,
+                    signature.library
+                );
+                user_symbols
+                    .entry(vram)
+                    .or_insert_with(|| naming::symbol(&signature.name));
+            }
+            log::info!("Segment Name: {}", segment.name);
+            log::debug!("Segment Type: {}", segment.segment_type);
+            log::debug!("Segment Start: {}", segment.start);
 
             if let Some(subsegments) = &segment.subsegments {
                 for subsegment in subsegments {
@@ -1691,51 +2942,73 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
                     let subsegment_start = subsegment.start;
                     let subsegment_end = subsegment.end.unwrap_or(file_contents.len() as u64);
 
-                    println!(
+                    log::debug!(
                         "subsegment {:08X}-{:08X} {} {}",
-                        subsegment_start, subsegment_end, subsegment_type, subsegment_file,
+                        subsegment_start,
+                        subsegment_end,
+                        subsegment_type,
+                        subsegment_file,
                     );
 
                     if subsegment_type == "data" || subsegment_type.starts_with('.') {
                         // just emit words
-                        let mut data_str = String::new();
-                        for i in (subsegment_start..subsegment_end).step_by(2) {
-                            let ii = i as usize;
-                            let data: u32 =
-                                ((file_contents[ii] as u32) << 8) | file_contents[ii + 1] as u32;
-                            data_str.push_str(&format!(
-                                "/* 0x{:08X} */ .word 0x{:04X}\n",
-                                i + segment.vram,
-                                data
-                            ));
-                        }
+                        let vaddr = (subsegment_start + segment.vram) as u32;
+                        let label = DataSymbol {
+                            vram: vaddr,
+                            size: (subsegment_end - subsegment_start) as u32,
+                            name: naming::data(vaddr),
+                        };
+                        let data_str = format!(
+                            "glabel {}\n{}",
+                            label.name,
+                            emit_data_words(
+                                file_contents,
+                                subsegment_start,
+                                subsegment_end,
+                                segment.vram,
+                            )
+                        );
 
                         let processed_section = ProcessedSection {
                             is_code: false,
+                            section_type: subsegment_type.clone(),
+                            segment: segment.name.clone(),
+                            file: subsegment_file.clone(),
                             disassembled_funcs: BTreeMap::<u32, DisassembledFunc>::new(),
                             data: data_str,
+                            labels: vec![label],
                             addr: subsegment_start,
+                            end: subsegment_end,
                             vaddr: subsegment_start + segment.vram,
                             vbase: segment.vram,
                         };
                         processed_sections.push(processed_section);
                     } else {
                         // find functions and process
-                        let disassembled_funcs = handle_code_section(
+                        let mut disassembled_funcs = handle_code_section(
                             file_contents,
                             subsegment_start,
                             subsegment_end,
                             segment.vram,
                             &user_symbols,
-                            &subsegment.function_ranges,
-                            &subsegment.data_ranges,
+                            subsegment,
                         );
+                        for func in disassembled_funcs.values_mut() {
+                            if let Some(signature) = library.get(&(func.addr as usize)) {
+                                func.library = Some(signature.clone());
+                            }
+                        }
 
                         let processed_section = ProcessedSection {
                             is_code: true,
+                            section_type: subsegment_type.clone(),
+                            segment: segment.name.clone(),
+                            file: subsegment_file.clone(),
                             disassembled_funcs: disassembled_funcs,
+                            labels: Vec::new(),
                             data: "".to_string(),
                             addr: subsegment_start,
+                            end: subsegment_end,
                             vaddr: subsegment_start + segment.vram,
                             vbase: segment.vram,
                         };
@@ -1746,7 +3019,16 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
         }
     }
 
-    let mut includes: HashSet<String> = HashSet::new();
+    if config.options.migrate_rodata_to_functions {
+        migrate_rodata_to_functions(file_contents, &mut processed_sections);
+    }
+    (processed_sections, bss_symbols)
+}
+
+// The asm ids and function names still pulled in by INCLUDE_ASM in any of the
+// config's C files, i.e. everything that has not been decompiled yet.
+fn find_includes(config: &Config) -> CIncludes {
+    let mut includes = CIncludes::default();
 
     // determine first what has been decompiled
     if let Some(segs) = &config.segments {
@@ -1757,7 +3039,7 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
             for seg in segs {
                 if let Some(subsegments) = &seg.subsegments {
                     for subseg in subsegments {
-                        println!("seg {:#?}", subseg);
+                        log::trace!("seg {:#?}", subseg);
                         // // need to check the designated file rather than just the segment
                         // // collect all c files specified in the yaml, then check all of those
                         // // and add to includes
@@ -1765,15 +3047,15 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
                         if let Some(subseg_file) = &subseg.file {
                             let c_filename = format!("{}/{}.c", path, subseg_file);
 
-                            println!("checking {}", c_filename);
+                            log::debug!("checking {}", c_filename);
                             if Path::new(&c_filename).exists() {
                                 match find_include_asm_in_c_file(&c_filename) {
                                     Ok(set) => {
-                                        println!("adding {:#?}", set);
+                                        log::trace!("adding {:#?}", set);
                                         includes.extend(set)
                                     }
                                     Err(err) => {
-                                        eprintln!("Error reading the file: {}", err);
+                                        log::warn!("Error reading {}: {}", c_filename, err);
                                     }
                                 }
                             }
@@ -1784,54 +3066,61 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
         }
     }
 
+    includes
+}
+
+fn handle_segments(
+    file_contents: &Vec<u8>,
+    config: &Config,
+    plan: &mut OutputPlan,
+) -> Result<(), Error> {
+    let (processed_sections, bss_symbols) = process_segments(file_contents, config);
+    let includes = find_includes(config);
+
     // all the segments are processed, emit files
 
-    std::fs::create_dir_all(&config.options.asm_path).expect("Failed to create directories.");
-    std::fs::create_dir_all(&format!("{}/f_nonmat", config.options.asm_path))
-        .expect("Failed to create directories.");
-    std::fs::create_dir_all(&format!("{}/f_match", config.options.asm_path))
-        .expect("Failed to create directories.");
-    std::fs::create_dir_all(&format!("{}/data", config.options.asm_path))
-        .expect("Failed to create directories.");
-
-    std::fs::create_dir_all(&config.options.ld_scripts_path)
-        .expect("Failed to create directories.");
-    std::fs::create_dir_all(&config.options.syms_path).expect("Failed to create directories.");
-    // emit all the asm
+    for dir in [
+        config.options.asm_path.clone(),
+        format!("{}/f_nonmat", config.options.asm_path),
+        format!("{}/f_match", config.options.asm_path),
+        format!("{}/data", config.options.asm_path),
+        config.options.ld_scripts_path.clone(),
+        config.options.syms_path.clone(),
+    ] {
+        plan.create_dir(&dir)?;
+    }
+    // emit all the asm, noting which files each C file INCLUDE_ASMs
+    let mut asm_deps = BTreeMap::<String, Vec<String>>::new();
     for processed_section in &processed_sections {
         if !processed_section.is_code {
-            emit_asm_file(
-                format!(
-                    "{}/data/d{:07X}.s",
-                    config.options.asm_path, processed_section.vaddr
-                ),
-                processed_section.data.clone(),
-            );
+            let asm_id = naming::data_file(processed_section.vaddr as u32);
+            let filename = format!("{}/data/{}.s", config.options.asm_path, asm_id);
+            plan.write(&filename, &format!("{}\n", processed_section.data))?;
+            if includes.contains(&asm_id) {
+                asm_deps
+                    .entry(processed_section.file.clone())
+                    .or_default()
+                    .push(filename);
+            }
         } else {
             for (_addr, df) in &processed_section.disassembled_funcs {
-                let func_name = format!("func_{:08X}", df.addr + processed_section.vbase as u32);
-                let asm_id = format!("f{:07X}", df.addr + processed_section.vbase as u32);
+                let func_name = naming::function(df.addr + processed_section.vbase as u32);
+                let asm_id = naming::function_file(df.addr + processed_section.vbase as u32);
 
                 if includes.contains(&func_name) || includes.contains(&asm_id) {
                     // this has not been decompiled
-                    emit_asm_file(
-                        format!(
-                            "{}/f_nonmat/f{:07X}.s",
-                            config.options.asm_path,
-                            df.addr + processed_section.vbase as u32
-                        ),
-                        df.text.clone(),
-                    );
+                    let filename = format!("{}/f_nonmat/{}.s", config.options.asm_path, asm_id);
+                    plan.write(&filename, &format!("{}\n", df.text))?;
+                    asm_deps
+                        .entry(processed_section.file.clone())
+                        .or_default()
+                        .push(filename);
                 } else {
                     // has been decompiled
-                    emit_asm_file(
-                        format!(
-                            "{}/f_match/f{:07X}.s",
-                            config.options.asm_path,
-                            df.addr + processed_section.vbase as u32
-                        ),
-                        df.text.clone(),
-                    );
+                    plan.write(
+                        &format!("{}/f_match/{}.s", config.options.asm_path, asm_id),
+                        &format!("{}\n", df.text),
+                    )?;
                 }
             }
         }
@@ -1840,7 +3129,39 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
     let path = &config.options.src_path;
     let asm_path = &config.options.asm_path;
 
-    std::fs::create_dir_all(path).expect("Failed to create src_path directories.");
+    plan.create_dir(path)?;
+    let include_path = config.options.include_path.as_ref().unwrap_or(path);
+    plan.create_dir(include_path)?;
+    plan.write(&format!("{}/inc_asm.h", include_path), INC_ASM_H)?;
+    plan.write(&format!("{}/macro.inc", include_path), MACRO_INC)?;
+    plan.write(
+        &format!("{}/saturn_regs.h", include_path),
+        &saturn::header(),
+    )?;
+
+    let segments = config.segments.as_deref().unwrap_or_default();
+    for (index, segment) in segments.iter().enumerate() {
+        let user_syms = format!(
+            "{}/{}_user_syms.txt",
+            config.options.syms_path, segment.name
+        );
+        let bss = segment_bss(segments, index, file_contents.len() as u64);
+        let segment_bss_symbols: Vec<_> = bss_symbols
+            .iter()
+            .filter(|(vram, _)| bss.as_ref().is_some_and(|bss| bss.contains(**vram)))
+            .map(|(vram, name)| (*vram, name.clone()))
+            .collect();
+        plan.write(
+            &format!("{}/{}_syms.h", include_path, segment.name),
+            &gen_extern_header(
+                &segment.name,
+                &processed_sections,
+                &segment_bss_symbols,
+                &read_user_symbols(&user_syms),
+                &read_user_prototypes(&user_syms),
+            ),
+        )?;
+    }
 
     if let Some(segs) = &config.segments {
         if !segs.is_empty() {
@@ -1848,20 +3169,28 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
             let base_addr = &segs[0].vram;
 
             let syms_filename = format!("{}/{}_syms.txt", &config.options.syms_path, segment_name);
-            let mut syms_file =
-                std::fs::File::create(syms_filename).expect("Failed to create file.");
+            let mut syms = String::new();
 
             let c_filename = format!("{}/{}.c", path, segment_name);
 
             // don't overwite the c file if it's already existing
             if !Path::new(&c_filename).exists() {
-                write_c_file(config, &path, &segment_name, &asm_path, &processed_sections);
+                write_c_file(
+                    plan,
+                    config,
+                    path,
+                    segment_name,
+                    asm_path,
+                    &processed_sections,
+                )?;
             }
 
             {
                 // The linker script is generated entirely from the YAML so it can be ephemeral.
                 let filename = format!("{}/{}.ld", &config.options.ld_scripts_path, segment_name);
-                let inputs = linker_inputs(&segs[0]);
+                let mut inputs = linker_inputs(&segs[0]);
+                order_linker_inputs(&mut inputs, &config.options.section_order);
+                let bss = segment_bss(segs, 0, file_contents.len() as u64);
                 let linker_script = gen_ld_script(
                     segment_name,
                     &format!("{:08X}", base_addr),
@@ -1869,12 +3198,14 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
                     &config.options.obj_path,
                     config.options.check_layout,
                     &inputs,
+                    bss.as_ref(),
                 );
-                let contents = format!("{}\n", linker_script);
-                if std::fs::read_to_string(&filename).ok().as_deref() != Some(&contents) {
-                    std::fs::write(filename, contents)
-                        .expect("Failed to write linker script file.");
-                }
+                plan.write(&filename, &format!("{}\n", linker_script))?;
+
+                plan.write(
+                    &format!("{}/build.ninja", &config.options.ld_scripts_path),
+                    &gen_build_ninja(config, &inputs, &filename, &asm_deps),
+                )?;
             }
 
             // write symbols
@@ -1888,65 +3219,274 @@ fn handle_segments(file_contents: &Vec<u8>, config: &Config) {
                             // need _ prefix for name mangling
                             // seems like all asm symbols need _ to be accessible
                             // from C
-                            writeln!(
-                                &mut syms_file,
-                                "_func_{:08X} = 0x{:08X};",
-                                pair.1.addr + processed_section.vbase as u32,
-                                pair.1.addr + processed_section.vbase as u32
-                            )
-                            .expect("Failed to write to file.");
+                            let addr = pair.1.addr + processed_section.vbase as u32;
+                            syms.push_str(&format!(
+                                "{} = 0x{:08X};\n",
+                                naming::symbol(&naming::function(addr)),
+                                addr
+                            ));
                         }
                     }
                 }
             }
-        }
-    }
-}
 
-use std::env;
+            for (addr, name) in &bss_symbols {
+                syms.push_str(&format!("{} = 0x{:08X};\n", name, addr));
+            }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+            // define the registers and BIOS vectors that literals were named after
+            let user_symbols = read_user_symbols(&format!(
+                "{}/{}_user_syms.txt",
+                config.options.syms_path, segment_name
+            ));
+            let funcs = processed_sections
+                .iter()
+                .flat_map(|section| section.disassembled_funcs.values());
+            let mut registers: BTreeMap<u32, String> = funcs
+                .clone()
+                .flat_map(|func| func.references.iter())
+                .filter_map(|&value| resolve_literal(value, &user_symbols))
+                .filter(|(addr, _)| !user_symbols.contains_key(addr))
+                .collect();
+            for &vector in funcs.flat_map(|func| func.bios_calls.iter()) {
+                if let Some(name) = saturn::bios_call_name(vector) {
+                    if !user_symbols.contains_key(&vector) {
+                        registers.insert(vector, name.to_string());
+                    }
+                }
+            }
+            for (addr, name) in registers {
+                syms.push_str(&format!("{} = 0x{:08X};\n", name, addr));
+            }
 
-    if let Some(filename) = args.get(1) {
-        if filename == "--find-funcs" {
-            if let Some(funcs_filename) = args.get(2) {
-                match read_file_to_vec(&funcs_filename) {
-                    Ok(file_contents) => {
-                        let mut ranges = Vec::<FunctionRange>::new();
-                        find_funcs(&file_contents, 0, file_contents.len() as u64, &mut ranges);
-                        for range in ranges {
-                            // println!("{:08X}-{:08X}", range.phys_start, range.phys_end);
-                            println!("0x{:08X},", range.phys_start + 0x06066000);
+            // and the library functions named by a signature
+            for section in &processed_sections {
+                for func in section.disassembled_funcs.values() {
+                    let addr = func.addr + section.vbase as u32;
+                    if let Some(signature) = &func.library {
+                        if !user_symbols.contains_key(&addr) {
+                            syms.push_str(&format!(
+                                "{} = 0x{:08X}; /* library {} */\n",
+                                naming::symbol(&signature.name),
+                                addr,
+                                signature.library
+                            ));
                         }
                     }
-                    Err(error) => {
-                        // Error reading the file
-                        println!("Error: {:?} {}", error, funcs_filename);
-                    }
                 }
             }
+            plan.write(&syms_filename, &syms)?;
+        }
+    }
+
+    plan.update_manifest(&config.options.asm_path)?;
+    for dir in ["f_nonmat", "f_match", "data"] {
+        plan.find_stale(&format!("{}/{}", config.options.asm_path, dir))?;
+    }
+    Ok(())
+}
+
+// Everything goes to stderr so stdout stays clean for find-funcs and disasm.
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
             return;
         }
-        println!("Reading: {}", filename);
-        let config = parse_yaml2(filename.to_string());
+        match record.level() {
+            log::Level::Info => eprintln!("{}", record.args()),
+            level => eprintln!("{}: {}", level.as_str().to_ascii_lowercase(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn level_filter(quiet: bool, verbose: u8) -> log::LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => log::LevelFilter::Error,
+        (false, 0) => log::LevelFilter::Info,
+        (false, 1) => log::LevelFilter::Debug,
+        (false, _) => log::LevelFilter::Trace,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde_derive::Serialize)]
+struct Diagnostic {
+    #[serde(serialize_with = "serialize_address")]
+    address: u32,
+    code: &'static str,
+    message: String,
+}
+
+fn serialize_address<S>(address: &u32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&format!("0x{:08X}", address))
+}
+
+thread_local! {
+    static DIAGNOSTICS: std::cell::RefCell<Vec<Diagnostic>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+// Warn about a questionable classification and keep it for the diagnostics file.
+fn diagnose(code: &'static str, address: u32, message: String) {
+    log::warn!("0x{:08X}: {}", address, message);
+    DIAGNOSTICS.with(|diagnostics| {
+        diagnostics.borrow_mut().push(Diagnostic {
+            address,
+            code,
+            message,
+        })
+    });
+}
+
+fn take_diagnostics() -> Vec<Diagnostic> {
+    let mut diagnostics = DIAGNOSTICS.with(|diagnostics| diagnostics.take());
+    diagnostics.sort();
+    diagnostics.dedup();
+    diagnostics
+}
+
+fn write_diagnostics(filename: &str, diagnostics: &[Diagnostic]) -> io::Result<()> {
+    let mut file = File::create(filename)?;
+    for diagnostic in diagnostics {
+        writeln!(file, "{}", serde_json::to_string(diagnostic)?)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Error {
+    // The YAML could not be read, parsed or failed validation.
+    Config {
+        filename: String,
+        problems: Vec<ConfigProblem>,
+    },
+    // The binary named by options.target_path could not be read.
+    TargetMissing {
+        path: String,
+        source: io::Error,
+    },
+    // Any other file that could not be read or written.
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    // The input was readable but cannot be analysed as asked.
+    Analysis(String),
+    // verify found a difference between the build and the target.
+    BuildMismatch {
+        path: String,
+        difference: String,
+    },
+}
+
+impl Error {
+    // Distinct codes so scripts can tell failures apart without parsing
+    // stderr. They follow sysexits.h where one fits; 2 is clap's usage error.
+    fn exit_code(&self) -> u8 {
+        match self {
+            Error::BuildMismatch { .. } => 1,
+            Error::Analysis(_) => 65,
+            Error::TargetMissing { .. } => 66,
+            Error::Io { .. } => 74,
+            Error::Config { .. } => 78,
+        }
+    }
+}
 
-        match read_file_to_vec(&config.options.target_path) {
-            Ok(file_contents) => {
-                handle_segments(&file_contents, &config);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config { filename, problems } => {
+                for (i, problem) in problems.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}: {}", filename, problem)?;
+                }
+                Ok(())
             }
-            Err(error) => {
-                // Error reading the file
-                println!("Error: {:?} {}", error, config.options.target_path);
+            Error::TargetMissing { path, source } => {
+                write!(f, "cannot read target {}: {}", path, source)
             }
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Analysis(message) => write!(f, "{}", message),
+            Error::BuildMismatch { path, difference } => write!(f, "{}: {}", path, difference),
         }
     }
 }
 
-use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use tempfile::NamedTempFile;
+trait WithPath<T> {
+    fn with_path(self, path: &str) -> Result<T, Error>;
+}
+
+impl<T> WithPath<T> for io::Result<T> {
+    fn with_path(self, path: &str) -> Result<T, Error> {
+        self.map_err(|source| Error::Io {
+            path: path.to_string(),
+            source,
+        })
+    }
+}
+
+// Parse and validate a config, and read its target. A target that cannot be
+// read is only reported once the config itself is known to be sound.
+fn load_config(filename: &str) -> Result<(Config, Vec<u8>), Error> {
+    let mut config = parse_yaml2(filename.to_string()).map_err(|problem| Error::Config {
+        filename: filename.to_string(),
+        problems: vec![problem],
+    })?;
+
+    let file_contents = read_file_to_vec(&config.options.target_path);
+    let problems = validate_config(&config, file_contents.as_deref().ok());
+    if !problems.is_empty() {
+        return Err(Error::Config {
+            filename: filename.to_string(),
+            problems,
+        });
+    }
+    let file_contents = file_contents.map_err(|source| Error::TargetMissing {
+        path: config.options.target_path.clone(),
+        source,
+    })?;
+    // every name generated from here on follows the config
+    naming::set(config.options.naming.clone());
+    config.signatures = load_signatures(&config.options.signatures).0;
+    Ok((config, file_contents))
+}
+
+fn read_binary(filename: &str) -> Result<Vec<u8>, Error> {
+    read_file_to_vec(filename).with_path(filename)
+}
+
+fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+    log::set_logger(&Logger).expect("logger is only set once");
+    log::set_max_level(level_filter(cli.quiet, cli.verbose));
+    let diagnostics_filename = cli.diagnostics.take();
+
+    let mut result = run(cli);
+    if let Some(filename) = diagnostics_filename {
+        let written = write_diagnostics(&filename, &take_diagnostics()).with_path(&filename);
+        // A failed run is the more useful error to report.
+        result = result.and(written);
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            log::error!("{}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
 
 fn assemble(input: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     // Create a temporary file and write the assembly code to it
@@ -1996,8 +3536,6 @@ fn assemble(input: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     Ok(binary)
 }
 
-use similar::{ChangeTag, TextDiff};
-
 fn print_diff(expected_lines: String, actual_lines: String) {
     let diff = TextDiff::from_lines(&expected_lines, &actual_lines);
 
@@ -2027,8 +3565,7 @@ fn asm_test_case(asm: String, expected: String, virtual_base_addr: u64) {
         output.len().try_into().unwrap(),
         virtual_base_addr,
         &HashMap::new(),
-        &[],
-        &[],
+        &Subsegment::default(),
     );
 
     let trimmed_right: String = expected
@@ -2046,123 +3583,96 @@ fn asm_test_case(asm: String, expected: String, virtual_base_addr: u64) {
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct LinkerInput {
-    start: u64,
-    object: String,
-    section: String,
-}
-
-fn linker_inputs(segment: &Segment) -> Vec<LinkerInput> {
-    let mut seen = HashSet::new();
-    let mut inputs = Vec::new();
-    let mut legacy_text_starts = HashMap::new();
-    let mut legacy_c_files = HashSet::new();
-
-    if let Some(subsegments) = &segment.subsegments {
-        for subsegment in subsegments {
-            if matches!(subsegment.segment_type.as_deref(), Some("data") | Some("c")) {
-                if let Some(file) = &subsegment.file {
-                    legacy_text_starts
-                        .entry(file.clone())
-                        .and_modify(|start: &mut u64| *start = (*start).min(subsegment.start))
-                        .or_insert(subsegment.start);
-                    if subsegment.segment_type.as_deref() == Some("c") {
-                        legacy_c_files.insert(file.clone());
-                    }
-                }
+// Declare every function and data symbol of a segment so decompiled C can
+// reference them. User symbols name them, and supply prototypes.
+fn gen_extern_header(
+    segment_name: &str,
+    processed_sections: &[ProcessedSection],
+    bss_symbols: &[(u32, String)],
+    user_symbols: &HashMap<u32, String>,
+    prototypes: &HashMap<u32, String>,
+) -> String {
+    let c_name = |vram: u32, name: String| match user_symbols.get(&vram) {
+        Some(symbol) => naming::c_name(symbol),
+        None => name,
+    };
+    let guard: String = segment_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
             }
-        }
-        for subsegment in subsegments {
-            let Some(file) = &subsegment.file else {
-                continue;
+        })
+        .collect();
+
+    let mut functions = BTreeMap::new();
+    let mut data = BTreeMap::new();
+    for section in processed_sections
+        .iter()
+        .filter(|section| section.segment == segment_name)
+    {
+        for func in section.disassembled_funcs.values() {
+            let vram = func.addr + section.vbase as u32;
+            let name = match &func.library {
+                Some(signature) => signature.name.clone(),
+                None => naming::function(vram),
             };
-            let section = match subsegment.segment_type.as_deref() {
-                Some("c") => ".text",
-                Some("data") if !legacy_c_files.contains(file) => ".text",
-                Some(".text") => ".text",
-                Some(".data") => ".data",
-                Some(".rodata") => ".rodata",
-                Some(".bss") => ".bss",
-                Some(".sbss") => ".sbss",
-                _ => continue,
+            let mut declaration = match prototypes.get(&vram) {
+                Some(prototype) => prototype.clone(),
+                None => format!("void {}();", c_name(vram, name)),
             };
-            if seen.insert((file.clone(), section)) {
-                inputs.push(LinkerInput {
-                    start: if matches!(subsegment.segment_type.as_deref(), Some("c") | Some("data"))
-                    {
-                        legacy_text_starts[file]
-                    } else {
-                        subsegment.start
-                    },
-                    object: format!("{}.o", file),
-                    section: section.to_string(),
-                });
+            if let Some(signature) = &func.library {
+                declaration.push_str(&format!(" /* library {} */", signature.library));
             }
+            functions.insert(vram, declaration);
         }
-    }
-
-    inputs
-}
-
-fn gen_ld_script(
-    zero_prefix: &str,
-    addr: &str,
-    subalign: u64,
-    obj_path: &str,
-    check_layout: bool,
-    inputs: &[LinkerInput],
-) -> String {
-    let mut code = String::new();
-
-    code.push_str("SECTIONS\n{\n");
-    code.push_str("    __romPos = 0;\n");
-    code.push_str("    _gp = 0x0;\n");
-    code.push_str(&format!("    {}_ROM_START = __romPos;\n", zero_prefix));
-    code.push_str(&format!(
-        "    {}_VRAM = ADDR(.{});\n",
-        zero_prefix, zero_prefix
-    ));
-    code.push_str(&format!(
-        "    .{} 0x{} : AT({}_ROM_START) SUBALIGN({})\n    {{\n",
-        zero_prefix, addr, zero_prefix, subalign
-    ));
-    code.push_str(&format!("        {}_TEXT_START = .;\n", zero_prefix));
-    for input in inputs {
-        if check_layout {
-            code.push_str(&format!(
-                "        ASSERT(. - ADDR(.{}) == 0x{:X}, \"{} {} starts at the wrong offset\");\n",
-                zero_prefix, input.start, input.object, input.section,
-            ));
+        for label in &section.labels {
+            data.insert(
+                label.vram,
+                format!(
+                    "extern unsigned char {}[0x{:X}];",
+                    c_name(label.vram, label.name.clone()),
+                    label.size
+                ),
+            );
         }
-        let path = if obj_path.is_empty() {
-            input.object.clone()
-        } else {
-            format!("{}/{}", obj_path.trim_end_matches('/'), input.object)
-        };
-        code.push_str(&format!("        {}({});\n", path, input.section));
     }
-    code.push_str(&format!("        {}_TEXT_END = .;\n", zero_prefix));
-    code.push_str(&format!(
-        "        {}_TEXT_SIZE = ABSOLUTE({}_TEXT_END - {}_TEXT_START);\n    }}\n",
-        zero_prefix, zero_prefix, zero_prefix
-    ));
-    code.push_str(&format!("    __romPos += SIZEOF(.{});\n", zero_prefix));
-    code.push_str("    __romPos = ALIGN(__romPos, 16);\n");
-    code.push_str(&format!("    {}_ROM_END = __romPos;\n", zero_prefix));
-    code.push_str(&format!("    {}_VRAM_END = .;\n", zero_prefix));
-    code.push_str("\n    /DISCARD/ :\n    {\n");
-    code.push_str("        *(*);\n    }\n");
-    code.push_str("}");
+    for (vram, symbol) in bss_symbols {
+        data.entry(*vram).or_insert_with(|| {
+            format!(
+                "extern unsigned char {}[];",
+                c_name(*vram, naming::c_name(symbol))
+            )
+        });
+    }
 
-    code
+    let mut header = format!(
+        "/* Generated by rust-dis split, changes are overwritten. */\n#ifndef {0}_SYMS_H\n#define {0}_SYMS_H\n\n",
+        guard
+    );
+    for declaration in functions.values() {
+        header.push_str(declaration);
+        header.push('\n');
+    }
+    if !functions.is_empty() && !data.is_empty() {
+        header.push('\n');
+    }
+    for declaration in data.values() {
+        header.push_str(declaration);
+        header.push('\n');
+    }
+    header.push_str("\n#endif\n");
+    header
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::{format_progress, progress_report, ProgressFormat};
 
-    fn words_bytes(words: &[u16]) -> Vec<u8> {
+    pub fn words_bytes(words: &[u16]) -> Vec<u8> {
         words.iter().flat_map(|word| word.to_be_bytes()).collect()
     }
 
@@ -2250,184 +3760,6 @@ mod tests {
         assert_eq!(ranges[0].phys_start, 4);
     }
 
-    #[test]
-    fn test_ld_script() {
-        let expected = r#"SECTIONS
-{
-    __romPos = 0;
-    _gp = 0x0;
-    zero_ROM_START = __romPos;
-    zero_VRAM = ADDR(.zero);
-    .zero 0x06004080 : AT(zero_ROM_START) SUBALIGN(2)
-    {
-        zero_TEXT_START = .;
-        build/saturn/zero.o(.text);
-        zero_TEXT_END = .;
-        zero_TEXT_SIZE = ABSOLUTE(zero_TEXT_END - zero_TEXT_START);
-    }
-    __romPos += SIZEOF(.zero);
-    __romPos = ALIGN(__romPos, 16);
-    zero_ROM_END = __romPos;
-    zero_VRAM_END = .;
-
-    /DISCARD/ :
-    {
-        *(*);
-    }
-}"#;
-
-        let actual = gen_ld_script(
-            "zero",
-            "06004080",
-            2,
-            "build/saturn",
-            false,
-            &[LinkerInput {
-                start: 0,
-                object: "zero.o".to_string(),
-                section: ".text".to_string(),
-            }],
-        );
-        print_diff(expected.to_string(), actual.clone());
-        assert!(expected == actual);
-    }
-
-    #[test]
-    fn test_linker_inputs_are_ordered_and_deduplicated() {
-        let segment = Segment {
-            name: "zero".to_string(),
-            segment_type: "code".to_string(),
-            start: 0,
-            vram: 0x06004080,
-            subalign: Some(4),
-            subsegments: Some(vec![
-                Subsegment {
-                    start: 8,
-                    end: Some(8),
-                    segment_type: Some("data".to_string()),
-                    file: Some("zero".to_string()),
-                    function_ranges: Vec::new(),
-                    data_ranges: Vec::new(),
-                },
-                Subsegment {
-                    start: 0,
-                    end: Some(16),
-                    segment_type: Some("c".to_string()),
-                    file: Some("zero".to_string()),
-                    function_ranges: Vec::new(),
-                    data_ranges: Vec::new(),
-                },
-                Subsegment {
-                    start: 16,
-                    end: Some(24),
-                    segment_type: Some("c".to_string()),
-                    file: Some("lib/spr/spr_1c".to_string()),
-                    function_ranges: Vec::new(),
-                    data_ranges: Vec::new(),
-                },
-                Subsegment {
-                    start: 24,
-                    end: Some(32),
-                    segment_type: Some("c".to_string()),
-                    file: Some("zero".to_string()),
-                    function_ranges: Vec::new(),
-                    data_ranges: Vec::new(),
-                },
-            ]),
-        };
-
-        assert_eq!(
-            linker_inputs(&segment),
-            vec![
-                LinkerInput {
-                    start: 0,
-                    object: "zero.o".to_string(),
-                    section: ".text".to_string(),
-                },
-                LinkerInput {
-                    start: 16,
-                    object: "lib/spr/spr_1c.o".to_string(),
-                    section: ".text".to_string(),
-                },
-            ]
-        );
-    }
-
-    #[test]
-    fn test_splat_style_named_sections_generate_in_yaml_order() {
-        let yaml = r#"
-options:
-  target_path: fixture.bin
-  asm_path: asm
-  src_path: src
-  obj_path: build
-  ld_scripts_path: build
-  syms_path: build
-  check_layout: true
-  decomp_empty_funcs: false
-segments:
-  - name: fixture
-    type: code
-    start: 0
-    vram: 0x06010000
-    subalign: 2
-    subsegments:
-      - [0x0, .data, header]
-      - [0x8, c, main]
-      - [0x20, .data, animations]
-      - [0x28, .rodata, tables]
-      - start: 0x30
-        type: data
-        file: raw_tail
-"#;
-        let config: Config = serde_yaml::from_str(yaml).unwrap();
-        let segment = &config.segments.as_ref().unwrap()[0];
-        let inputs = linker_inputs(segment);
-
-        assert_eq!(
-            inputs,
-            vec![
-                LinkerInput {
-                    start: 0,
-                    object: "header.o".to_string(),
-                    section: ".data".to_string()
-                },
-                LinkerInput {
-                    start: 8,
-                    object: "main.o".to_string(),
-                    section: ".text".to_string()
-                },
-                LinkerInput {
-                    start: 0x20,
-                    object: "animations.o".to_string(),
-                    section: ".data".to_string()
-                },
-                LinkerInput {
-                    start: 0x28,
-                    object: "tables.o".to_string(),
-                    section: ".rodata".to_string()
-                },
-                LinkerInput {
-                    start: 0x30,
-                    object: "raw_tail.o".to_string(),
-                    section: ".text".to_string()
-                },
-            ]
-        );
-
-        let script = gen_ld_script("fixture", "06010000", 2, "build", true, &inputs);
-        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x0"));
-        assert!(script.contains("build/header.o(.data);"));
-        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x8"));
-        assert!(script.contains("build/main.o(.text);"));
-        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x20"));
-        assert!(script.contains("build/animations.o(.data);"));
-        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x28"));
-        assert!(script.contains("build/tables.o(.rodata);"));
-        assert!(script.contains("ASSERT(. - ADDR(.fixture) == 0x30"));
-        assert!(script.contains("build/raw_tail.o(.text);"));
-    }
-
     fn test_base_mov_l(expected: String, base: u64) {
         let asm = r#"
         mov.l r8, @-r15
@@ -2750,7 +4082,7 @@ segments:
 
     #[test]
     fn test_parse_tt_000_yaml() {
-        let config = parse_yaml2("./config.yaml".to_string());
+        let config = parse_yaml2("./config.yaml".to_string()).unwrap();
 
         let segments = config.segments.expect("Missing segments");
 
@@ -2808,8 +4140,10 @@ segments:
             bytes.len() as u64,
             0x06000000,
             &HashMap::new(),
-            &[[2, 20]],
-            &[],
+            &Subsegment {
+                function_ranges: vec![[2, 20]],
+                ..Default::default()
+            },
         );
 
         assert!(funcs.contains_key(&2));
@@ -2827,8 +4161,10 @@ segments:
             bytes.len() as u64,
             0x06000000,
             &HashMap::new(),
-            &[[0, 8]],
-            &[],
+            &Subsegment {
+                function_ranges: vec![[0, 8]],
+                ..Default::default()
+            },
         );
 
         assert!(funcs.contains_key(&0));
@@ -2856,14 +4192,118 @@ segments:
             bytes.len() as u64,
             0x06000000,
             &HashMap::new(),
-            &[[0, 12]],
-            &[[6, 9]],
+            &Subsegment {
+                function_ranges: vec![[0, 12]],
+                data_ranges: vec![[6, 9]],
+                ..Default::default()
+            },
         );
 
         assert!(funcs[&0].text.contains("/* 0x06000006 */ .word 0x1234"));
         assert!(funcs[&0].text.contains("/* 0x06000008 */ .word 0x5678"));
     }
 
+    #[test]
+    fn test_literals_name_saturn_registers_unless_user_symbol() {
+        let bytes = words_bytes(&[
+            0x2fe6, 0xd102, 0xd202, 0x000b, 0x0009, 0x0009, 0x25fe, 0x00a0, 0xffff, 0xfe10,
+        ]);
+        let user_symbols = HashMap::from([(0xFFFF_FE10, "_frt_tier".to_string())]);
+
+        let funcs = handle_code_section(
+            &bytes,
+            0,
+            bytes.len() as u64,
+            0x06000000,
+            &user_symbols,
+            &Subsegment {
+                function_ranges: vec![[0, 0x13]],
+                ..Default::default()
+            },
+        );
+
+        let text = &funcs[&0].text;
+        assert!(
+            text.contains("mov.l .Ldat_0600000C,r1 /* SCU_IMS */"),
+            "{}",
+            text
+        );
+        assert!(text.contains(".long SCU_IMS\n"));
+        assert!(text.contains(".long _frt_tier\n"));
+    }
+
+    #[test]
+    fn test_bios_calls_are_named_through_their_vector() {
+        let mut words = vec![
+            0x4f22, 0xd003, 0x6002, 0x400b, 0x0009, 0x4f26, 0x000b, 0x0009,
+        ];
+        words.extend([0x0600, 0x0340]);
+        let bytes = words_bytes(&words);
+        // the same vector loaded without a call through it
+        let no_call = words_bytes(&[0xd101, 0x000b, 0x0009, 0x0009, 0x0600, 0x0340]);
+
+        let funcs = handle_code_section(
+            &bytes,
+            0,
+            bytes.len() as u64,
+            0x06004000,
+            &HashMap::new(),
+            &Subsegment {
+                function_ranges: vec![[0, 0x13]],
+                ..Default::default()
+            },
+        );
+        let plain = handle_code_section(
+            &no_call,
+            0,
+            no_call.len() as u64,
+            0x06004000,
+            &HashMap::new(),
+            &Subsegment {
+                function_ranges: vec![[0, 0xb]],
+                ..Default::default()
+            },
+        );
+
+        let text = &funcs[&0].text;
+        assert!(text.contains("jsr @r0 /* BIOS SYS_CHGSCUIM */"), "{}", text);
+        assert!(text.contains(".long SYS_CHGSCUIM\n"));
+        assert_eq!(funcs[&0].bios_calls, vec![0x06000340]);
+        assert!(plain[&0].text.contains(".long 0x06000340\n"));
+    }
+
+    #[test]
+    fn test_gbr_operands_get_addresses_from_ldc_or_config() {
+        let bytes = words_bytes(&[
+            0xd102, 0x411e, 0xc501, 0xcd0f, 0x000b, 0x0009, 0x25f8, 0x0000, // ldc r1,gbr
+            0xc602, 0x000b, 0x0009, 0x0009, // gbr from the config
+        ]);
+        let hints = Subsegment {
+            function_ranges: vec![[0, 0xf], [0x10, 0x17]],
+            gbr: vec![[0x10, 0x10, 0x25FE0000]],
+            ..Default::default()
+        };
+
+        let funcs = handle_code_section(
+            &bytes,
+            0,
+            bytes.len() as u64,
+            0x06004000,
+            &HashMap::new(),
+            &hints,
+        );
+
+        let text = &funcs[&0].text;
+        assert!(
+            text.contains("ldc r1, gbr /* gbr = 0x25F80000 VDP2_TVMD */"),
+            "{}",
+            text
+        );
+        assert!(text.contains("gbr),r0 /* 0x25F80002 VDP2_EXTEN */"));
+        assert!(text.contains("/* 0x25F80000 VDP2_TVMD + r0 */"));
+        assert!(funcs[&0x10].text.contains("/* 0x25FE0008 SCU_D0C */"));
+    }
+
     #[test]
     fn test_mova_jump_table() {
         let mut bytes = vec![0u8; 0x40];
@@ -3042,4 +4482,932 @@ segments:
         );
         assert_eq!(indexed, "mov.w @(r0,r1),r1");
     }
+
+    #[test]
+    fn test_options_accept_yes_no_and_section_order() {
+        let yaml = r#"
+target_path: fixture.bin
+asm_path: asm
+src_path: src
+ld_scripts_path: build
+syms_path: build
+decomp_empty_funcs: false
+migrate_rodata_to_functions: yes
+section_order:
+  - ".rodata"
+  - ".text"
+"#;
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert!(options.migrate_rodata_to_functions);
+        assert_eq!(options.section_order, vec![".rodata", ".text"]);
+    }
+
+    #[test]
+    fn test_partition_rodata_assigns_exclusive_chunks() {
+        let mut references = BTreeMap::<u32, BTreeSet<u32>>::new();
+        references.entry(0x100).or_default().insert(0x10);
+        references.entry(0x108).or_default().insert(0x10);
+        references.entry(0x108).or_default().insert(0x20);
+        references.entry(0x10c).or_default().insert(0x20);
+        references.entry(0x200).or_default().insert(0x20);
+
+        assert_eq!(
+            partition_rodata(0x100, 0x110, &references),
+            vec![
+                RodataChunk {
+                    start: 0x100,
+                    end: 0x108,
+                    owner: Some(0x10),
+                },
+                RodataChunk {
+                    start: 0x108,
+                    end: 0x10c,
+                    owner: None,
+                },
+                RodataChunk {
+                    start: 0x10c,
+                    end: 0x110,
+                    owner: Some(0x20),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partition_rodata_keeps_chunks_word_aligned() {
+        let mut references = BTreeMap::<u32, BTreeSet<u32>>::new();
+        references.entry(0x100).or_default().insert(0x10);
+        // a string at an odd address, loaded by the same function and another
+        references.entry(0x103).or_default().insert(0x10);
+        references.entry(0x104).or_default().insert(0x20);
+        references.entry(0x109).or_default().insert(0x30);
+
+        let chunks = partition_rodata(0x100, 0x10c, &references);
+        assert_eq!(
+            chunks,
+            vec![
+                RodataChunk {
+                    start: 0x100,
+                    end: 0x104,
+                    owner: Some(0x10),
+                },
+                RodataChunk {
+                    start: 0x104,
+                    end: 0x10c,
+                    owner: None,
+                },
+            ]
+        );
+
+        // the chunks reassemble the words of the subsegment
+        let bytes: Vec<u8> = (0..0x10c).map(|i| i as u8).collect();
+        let words: String = chunks
+            .iter()
+            .map(|chunk| emit_data_words(&bytes, chunk.start as u64, chunk.end as u64, 0))
+            .collect();
+        assert_eq!(words, emit_data_words(&bytes, 0x100, 0x10c, 0));
+    }
+
+    #[test]
+    fn test_rodata_is_migrated_into_referencing_function() {
+        // mov.l r8,@-r15; mov.l @(4,pc),r1; rts; mov.l @r15+,r8; .long 0x06000010
+        let bytes = words_bytes(&[
+            0x2f86, 0xd101, 0x000b, 0x68f6, 0x0600, 0x0010, 0x0009, 0x0009, 0x1234, 0x5678,
+        ]);
+        let funcs = handle_code_section(
+            &bytes,
+            0,
+            0x10,
+            0x06000000,
+            &HashMap::new(),
+            &Subsegment {
+                function_ranges: vec![[0, 0x0f]],
+                ..Default::default()
+            },
+        );
+        let mut sections = vec![
+            ProcessedSection {
+                is_code: true,
+                section_type: "c".to_string(),
+                disassembled_funcs: funcs,
+                addr: 0,
+                end: 0x10,
+                vaddr: 0x06000000,
+                vbase: 0x06000000,
+                ..Default::default()
+            },
+            ProcessedSection {
+                is_code: false,
+                section_type: ".rodata".to_string(),
+                data: emit_data_words(&bytes, 0x10, 0x14, 0x06000000),
+                addr: 0x10,
+                end: 0x14,
+                vaddr: 0x06000010,
+                vbase: 0x06000000,
+                ..Default::default()
+            },
+        ];
+
+        migrate_rodata_to_functions(&bytes, &mut sections);
+
+        let text = &sections[0].disassembled_funcs[&0].text;
+        assert!(text.contains(".section .rodata\nglabel D_06000010\n"));
+        assert!(text.contains("/* 0x06000012 */ .word 0x5678\n.text\n"));
+        assert!(sections[1].data.is_empty());
+    }
+
+    #[test]
+    fn test_rodata_only_migrates_where_the_file_layout_keeps_it() {
+        let bytes = vec![0u8; 0x50];
+        let func = |addr: u32, references: &[u32]| {
+            (
+                addr,
+                DisassembledFunc {
+                    addr,
+                    references: references.to_vec(),
+                    ..Default::default()
+                },
+            )
+        };
+        let code = |file: &str, start: u64, end: u64, funcs: Vec<(u32, DisassembledFunc)>| {
+            ProcessedSection {
+                is_code: true,
+                section_type: "c".to_string(),
+                file: file.to_string(),
+                disassembled_funcs: funcs.into_iter().collect(),
+                addr: start,
+                end,
+                vaddr: 0x06000000 + start,
+                vbase: 0x06000000,
+                ..Default::default()
+            }
+        };
+        // rodata chunks at 0x20, 0x24, 0x28, 0x2c and 0x30, the one at 0x28
+        // loaded by the first function again and the one at 0x2c by another file
+        let mut sections = vec![
+            code(
+                "main",
+                0,
+                0x20,
+                vec![
+                    func(0x00, &[0x06000020, 0x06000028]),
+                    func(0x10, &[0x06000024]),
+                ],
+            ),
+            ProcessedSection {
+                is_code: false,
+                section_type: ".rodata".to_string(),
+                file: "main".to_string(),
+                addr: 0x20,
+                end: 0x34,
+                vaddr: 0x06000020,
+                vbase: 0x06000000,
+                ..Default::default()
+            },
+            code("main", 0x34, 0x40, vec![func(0x34, &[0x06000030])]),
+            code("other", 0x40, 0x50, vec![func(0x40, &[0x0600002C])]),
+        ];
+
+        migrate_rodata_to_functions(&bytes, &mut sections);
+
+        // the file's assembly in the order it is emitted
+        let emitted = [
+            &sections[0].disassembled_funcs[&0x00].text,
+            &sections[0].disassembled_funcs[&0x10].text,
+            &sections[1].data,
+            &sections[2].disassembled_funcs[&0x34].text,
+        ];
+        let labels: Vec<&str> = emitted
+            .iter()
+            .flat_map(|text| text.lines())
+            .filter_map(|line| line.strip_prefix("glabel "))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "D_06000020",
+                "D_06000024",
+                "D_06000028",
+                "D_0600002C",
+                "D_06000030"
+            ]
+        );
+        assert!(sections[0].disassembled_funcs[&0x00]
+            .text
+            .contains(".section .rodata\nglabel D_06000020\n"));
+        assert!(sections[2].disassembled_funcs[&0x34]
+            .text
+            .contains(".section .rodata\nglabel D_06000030\n"));
+        assert!(sections[3].disassembled_funcs[&0x40].text.is_empty());
+    }
+
+    #[test]
+    fn test_validate_config_reports_section_order_against_addresses() {
+        let mut yaml = NamedTempFile::new().unwrap();
+        write!(
+            yaml,
+            r#"options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+  section_order:
+    - .text
+    - .rodata
+segments:
+  - name: fixture
+    type: code
+    start: 0
+    vram: 0x06010000
+    subsegments:
+      - [0x0, .text, main]
+      - [0x10, .rodata, main]
+      - [0x20, .text, sub]
+"#
+        )
+        .unwrap();
+        let mut config = parse_yaml2(yaml.path().to_str().unwrap().to_string()).unwrap();
+
+        assert_eq!(
+            validate_config(&config, None),
+            vec![ConfigProblem {
+                line: Some(9),
+                field: "options.section_order[0]".to_string(),
+                message: "fixture links sub.o .text at 0x20 before main.o .rodata at 0x10"
+                    .to_string(),
+            }]
+        );
+        config.options.section_order.reverse();
+        assert_eq!(validate_config(&config, None).len(), 1);
+        config.options.section_order.clear();
+        assert!(validate_config(&config, None).is_empty());
+    }
+
+    #[test]
+    fn test_bss_defaults_to_end_of_loaded_data() {
+        let segment: Segment = serde_yaml::from_str(
+            "name: zero\ntype: code\nstart: 0\nvram: 0x06004000\nbss_size: 0x100\nsubsegments:\n  - [0x0, c, zero]\n  - start: 0x40\n    end: 0x80\n    type: .data\n    file: zero\n",
+        )
+        .unwrap();
+
+        let bss = segment_bss(&[segment], 0, 0x1000).unwrap();
+
+        assert_eq!(
+            bss,
+            BssRange {
+                vram: 0x06004080,
+                size: 0x100
+            }
+        );
+        assert!(bss.contains(0x06004080));
+        assert!(!bss.contains(0x06004180));
+    }
+
+    #[test]
+    fn test_open_ended_segment_stops_at_the_next_one() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+segments:
+  - name: first
+    type: code
+    start: 0
+    vram: 0x06004000
+    bss_size: 0x100
+    subsegments:
+      - [0x0, c, first]
+  - name: second
+    type: code
+    start: 0x8
+    vram: 0x06010000
+    subsegments:
+      - [0x8, c, second]
+"#,
+        )
+        .unwrap();
+        let segments = config.segments.as_deref().unwrap();
+        assert_eq!(segment_end(segments, 0, 0x10), 0x8);
+        assert_eq!(segment_end(segments, 1, 0x10), 0x10);
+        assert_eq!(segment_bss(segments, 0, 0x10).unwrap().vram, 0x06004008);
+
+        let target = (0u8..16).collect::<Vec<_>>();
+        let mut built = target.clone();
+        built[0xa] = 0xff;
+        assert!(check_build(&config, Some(&target), &built)
+            .unwrap()
+            .starts_with("segment second (0x8-0x10) differs"));
+
+        let mut config = config;
+        config.segments.as_mut().unwrap()[0].sha1 = Some(sha1_hex(&target[..8]));
+        assert!(validate_config(&config, Some(&target)).is_empty());
+    }
+
+    #[test]
+    fn test_bss_literal_references_become_d_symbols() {
+        // two mov.l @(0,pc),r1 loads, one of them pointing into bss
+        let bytes = words_bytes(&[
+            0xd100, 0x0009, 0x0600, 0x4090, 0xd100, 0x0009, 0x0600, 0x0000,
+        ]);
+        let bss = BssRange {
+            vram: 0x06004080,
+            size: 0x100,
+        };
+
+        let symbols = bss_literal_symbols(&bytes, 0, bytes.len() as u64, &bss);
+
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[&0x06004090], "_D_06004090");
+
+        // bss has its own template, renaming it leaves rodata alone
+        naming::set(naming::Naming {
+            bss: "B_{vram:08X}".to_string(),
+            ..Default::default()
+        });
+        let symbols = bss_literal_symbols(&bytes, 0, bytes.len() as u64, &bss);
+        assert_eq!(symbols[&0x06004090], "_B_06004090");
+        assert_eq!(naming::rodata(0x06004090), "D_06004090");
+        naming::set(naming::Naming::default());
+    }
+
+    #[test]
+    fn test_cache_through_literals_resolve_to_the_cached_symbol() {
+        let bytes = words_bytes(&[0xd100, 0x0009, 0x2607, 0x0000]);
+        let bss = BssRange {
+            vram: 0x06070000,
+            size: 0x100,
+        };
+
+        let symbols: HashMap<u32, String> = bss_literal_symbols(&bytes, 0, 8, &bss)
+            .into_iter()
+            .collect();
+
+        assert_eq!(symbols[&0x06070000], "_D_06070000");
+        assert_eq!(
+            format_literal(0x26070000, &symbols, true),
+            "_D_06070000 + 0x20000000"
+        );
+        assert_eq!(
+            format_literal(0x05FE00A0, &symbols, true),
+            "SCU_IMS - 0x20000000"
+        );
+        // a float constant, not the associative purge area
+        assert_eq!(format_literal(0x46070000, &symbols, true), "0x46070000");
+        let float = words_bytes(&[0xd100, 0x0009, 0x4607, 0x0010]);
+        assert!(bss_literal_symbols(&float, 0, 8, &bss).is_empty());
+    }
+
+    #[test]
+    fn test_validate_config_reports_every_problem_with_line() {
+        let mut yaml = NamedTempFile::new().unwrap();
+        write!(
+            yaml,
+            r#"options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+segments:
+  - name: fixture
+    type: code
+    start: 0
+    vram: 0x06010000
+    subsegments:
+      - [0x0, .data, header]
+      - start: 0x8
+        end: 0x40
+        type: c
+        file: main
+        function_ranges:
+          - [0x3, 0x10]
+      - [0x20, .data, anim]
+"#
+        )
+        .unwrap();
+        let config = parse_yaml2(yaml.path().to_str().unwrap().to_string()).unwrap();
+
+        let problems = validate_config(&config, Some(&[0u8; 0x30]));
+
+        assert_eq!(
+            problems,
+            vec![
+                ConfigProblem {
+                    line: Some(16),
+                    field: "segments[0].subsegments[1].end".to_string(),
+                    message: "0x40 is beyond the end of fixture.bin (0x30 bytes)".to_string(),
+                },
+                ConfigProblem {
+                    line: Some(16),
+                    field: "segments[0].subsegments[1].end".to_string(),
+                    message: "0x40 overlaps the next subsegment at 0x20".to_string(),
+                },
+                ConfigProblem {
+                    line: Some(20),
+                    field: "segments[0].subsegments[1].function_ranges[0]".to_string(),
+                    message: "0x3 is not 2-byte aligned".to_string(),
+                },
+                ConfigProblem {
+                    line: Some(20),
+                    field: "segments[0].subsegments[1].function_ranges[0]".to_string(),
+                    message: "[0x3, 0x10] is outside the subsegment 0x8-0x40".to_string(),
+                },
+            ]
+        );
+        assert!(validate_config(&config, None).len() == 3);
+    }
+
+    #[test]
+    fn test_validate_config_checks_target_sha1() {
+        let mut config: Config = serde_yaml::from_str(
+            "options:\n  target_path: fixture.bin\n  asm_path: asm\n  src_path: src\n  ld_scripts_path: build\n  syms_path: build\n  decomp_empty_funcs: false\n  sha1: A9993E364706816ABA3E25717850C26C9CD0D89D\n",
+        )
+        .unwrap();
+
+        assert!(validate_config(&config, Some(b"abc")).is_empty());
+
+        config.options.sha1 = Some("0000".to_string());
+        let problems = validate_config(&config, Some(b"abc"));
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].field, "options.sha1");
+    }
+
+    #[test]
+    fn test_parse_error_reports_yaml_line() {
+        let mut yaml = NamedTempFile::new().unwrap();
+        write!(yaml, "options:\n  target_path: [\n").unwrap();
+
+        let problem = parse_yaml2(yaml.path().to_str().unwrap().to_string()).unwrap_err();
+
+        assert!(problem.line.is_some());
+    }
+
+    #[test]
+    fn test_check_build_reports_first_differing_segment() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+segments:
+  - name: header
+    type: code
+    start: 0
+    vram: 0x06010000
+    subsegments:
+      - [0x0, .data, header]
+      - start: 0x4
+        end: 0x8
+        type: .data
+        file: header
+  - name: main
+    type: code
+    start: 0x8
+    vram: 0x06010008
+    subsegments:
+      - start: 0x8
+        end: 0x10
+        type: c
+        file: main
+"#,
+        )
+        .unwrap();
+        let target = (0u8..16).collect::<Vec<_>>();
+        let mut built = target.clone();
+
+        assert_eq!(check_build(&config, Some(&target), &built), None);
+
+        built[0xa] = 0xff;
+        let difference = check_build(&config, Some(&target), &built).unwrap();
+        assert!(difference.starts_with("segment main (0x8-0x10) differs"));
+        assert!(difference.ends_with("first difference at 0xA"));
+
+        assert!(check_build(&config, Some(&target), &target[..12])
+            .unwrap()
+            .starts_with("segment main (0x8-0x10) is missing"));
+    }
+
+    #[test]
+    fn test_validate_config_checks_segment_sha1() {
+        let config: Config = serde_yaml::from_str(
+            "options:\n  target_path: fixture.bin\n  asm_path: asm\n  src_path: src\n  ld_scripts_path: build\n  syms_path: build\n  decomp_empty_funcs: false\nsegments:\n  - name: main\n    type: code\n    start: 0x2\n    vram: 0x06010000\n    sha1: 81fe8bfe87576c3ecb22426f8e57847382917acf\n    subsegments:\n      - start: 0x2\n        end: 0x6\n        type: c\n        file: main\n",
+        )
+        .unwrap();
+
+        assert!(validate_config(&config, Some(b"--abcd")).is_empty());
+        assert_eq!(
+            validate_config(&config, Some(b"--abce"))[0].field,
+            "segments[0].sha1"
+        );
+    }
+
+    #[test]
+    fn test_verbosity_flags_select_log_level() {
+        assert_eq!(level_filter(true, 0), log::LevelFilter::Error);
+        assert_eq!(level_filter(false, 0), log::LevelFilter::Info);
+        assert_eq!(level_filter(false, 1), log::LevelFilter::Debug);
+        assert_eq!(level_filter(false, 2), log::LevelFilter::Trace);
+        assert!(Cli::try_parse_from(["rust-dis", "-q", "-v", "info", "config.yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_questionable_classifications_are_diagnosed() {
+        take_diagnostics();
+        // mov.l @(0,pc),r1 loads a pool entry that sits before the rts,
+        // and 0xFFFF is not an instruction
+        let bytes = words_bytes(&[0x2f86, 0xd100, 0x1234, 0x5678, 0xffff, 0x000b, 0x68f6]);
+
+        handle_code_section(
+            &bytes,
+            0,
+            bytes.len() as u64,
+            0x06000000,
+            &HashMap::new(),
+            &Subsegment {
+                function_ranges: vec![[0, 12]],
+                ..Default::default()
+            },
+        );
+
+        let diagnostics = take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.code, diagnostic.address))
+                .collect::<Vec<_>>(),
+            vec![
+                ("data-in-function", 0x06000004),
+                ("unknown-instruction", 0x06000008)
+            ]
+        );
+
+        let file = NamedTempFile::new().unwrap();
+        write_diagnostics(file.path().to_str().unwrap(), &diagnostics).unwrap();
+        let lines = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(
+            lines.lines().nth(1).unwrap(),
+            r#"{"address":"0x06000008","code":"unknown-instruction","message":"0xFFFF in func_06000000 is not an SH-2 instruction"}"#
+        );
+    }
+
+    #[test]
+    fn test_load_config_tells_broken_config_from_missing_target() {
+        let mut yaml = NamedTempFile::new().unwrap();
+        write!(
+            yaml,
+            "options:\n  target_path: /nonexistent/target.bin\n  asm_path: asm\n  src_path: src\n  ld_scripts_path: build\n  syms_path: build\n  decomp_empty_funcs: false\n"
+        )
+        .unwrap();
+        let filename = yaml.path().to_str().unwrap().to_string();
+
+        let error = load_config(&filename).unwrap_err();
+        assert!(matches!(error, Error::TargetMissing { .. }));
+        assert_eq!(error.exit_code(), 66);
+
+        writeln!(yaml, "segments: 5").unwrap();
+        let error = load_config(&filename).unwrap_err();
+        assert!(matches!(error, Error::Config { .. }));
+        assert_eq!(error.exit_code(), 78);
+    }
+
+    #[test]
+    fn test_io_error_names_the_path() {
+        let error = OutputPlan::default()
+            .write("/nonexistent/dir/f6000000.s", "")
+            .unwrap_err();
+
+        assert_eq!(error.exit_code(), 74);
+        assert!(error
+            .to_string()
+            .starts_with("/nonexistent/dir/f6000000.s: "));
+    }
+
+    #[test]
+    fn test_c_scan_skips_comments_and_classifies_preprocessor_blocks() {
+        let includes = scan_c_source(
+            r#"#include "inc_asm.h"
+// INCLUDE_ASM("asm", f6000000, func_06000000);
+/* INCLUDE_ASM("asm", f6000010, func_06000010); */
+#if 0
+INCLUDE_ASM("asm", f6000020, func_06000020);
+#endif
+#ifndef NON_MATCHING
+INCLUDE_ASM("asm", f6000030, func_06000030);
+#else
+void func_06000030(void) {}
+#endif
+INCLUDE_ASM("asm",
+            f6000040,
+            func_06000040);
+#if 0
+#else
+INCLUDE_ASM("asm", f6000050, func_06000050); // done soon
+#endif
+const char *s = "/* INCLUDE_ASM("asm", f6000060, func_06000060); */";
+"#,
+        );
+
+        for hidden in ["f6000000", "f6000010", "f6000020", "f6000060"] {
+            assert!(!includes.contains(hidden), "{}", hidden);
+        }
+        assert_eq!(
+            includes.non_matching,
+            HashSet::from(["f6000030".to_string(), "func_06000030".to_string()])
+        );
+        assert!(includes.asm_only.contains("f6000040"));
+        assert!(includes.asm_only.contains("func_06000050"));
+        assert_eq!(includes.asm_only.len(), 4);
+    }
+
+    #[test]
+    fn test_c_file_has_non_matching_skeleton_and_rodata_includes() {
+        let config: Config = serde_yaml::from_str(
+            "options:\n  target_path: t.bin\n  asm_path: asm\n  src_path: src\n  ld_scripts_path: build\n  syms_path: build\n  decomp_empty_funcs: false\n  non_matching: [0x06000010]\n",
+        )
+        .unwrap();
+        let func = |addr: u32| DisassembledFunc {
+            addr,
+            end: addr + 0x10,
+            name: format!("f{:07X}", addr + 0x06000000),
+            ..Default::default()
+        };
+        let sections = vec![
+            ProcessedSection {
+                is_code: true,
+                disassembled_funcs: BTreeMap::from([(0, func(0)), (0x10, func(0x10))]),
+                vbase: 0x06000000,
+                ..Default::default()
+            },
+            ProcessedSection {
+                section_type: ".rodata".to_string(),
+                vaddr: 0x06000020,
+                ..Default::default()
+            },
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().display().to_string();
+
+        write_c_file(
+            &mut OutputPlan::default(),
+            &config,
+            &src,
+            "main",
+            "asm",
+            &sections,
+        )
+        .unwrap();
+
+        let c = std::fs::read_to_string(format!("{}/main.c", src)).unwrap();
+        assert_eq!(
+            c,
+            "#include \"inc_asm.h\"\n\
+             INCLUDE_ASM(\"asm\", f6000000, func_06000000);\n\
+             #ifndef NON_MATCHING\n\
+             INCLUDE_ASM(\"asm\", f6000010, func_06000010);\n\
+             #else\n\
+             void func_06000010(void) {\n\
+             }\n\
+             #endif\n\
+             INCLUDE_RODATA(\"asm\", d6000020);\n"
+        );
+        let includes = scan_c_source(&c);
+        assert!(includes.non_matching.contains("func_06000010"));
+        assert!(includes.asm_only.contains("func_06000000"));
+    }
+
+    #[test]
+    fn test_naming_templates_reach_asm_and_validation() {
+        naming::set(naming::Naming {
+            function: "fn_{vram:08X}".to_string(),
+            function_file: "fn{vram:06X}".to_string(),
+            ..Default::default()
+        });
+        // mov.l r8,@-r15; rts; mov.l @r15+,r8
+        let bytes = words_bytes(&[0x2f86, 0x000b, 0x68f6, 0x0009]);
+
+        let hints = Subsegment {
+            function_ranges: vec![[0, 7]],
+            ..Default::default()
+        };
+        let funcs = handle_code_section(&bytes, 0, 8, 0x06000000, &HashMap::new(), &hints);
+        naming::set(naming::Naming::default());
+
+        assert_eq!(funcs[&0].name, "fn6000000");
+        assert!(funcs[&0].text.starts_with("glabel fn_06000000\n"));
+
+        let mut yaml = NamedTempFile::new().unwrap();
+        write!(
+            yaml,
+            r#"options:
+  target_path: fixture.bin
+  asm_path: asm
+  src_path: src
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+  naming:
+    dos_file_names: true
+segments:
+  - name: main
+    type: code
+    start: 0
+    vram: 0x06000000
+    subsegments:
+      - [0x0, c, game/longfilename]
+"#
+        )
+        .unwrap();
+        let config = parse_yaml2(yaml.path().to_str().unwrap().to_string()).unwrap();
+
+        let problems = validate_config(&config, Some(&bytes));
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            problems[0].to_string(),
+            "line 16: segments[0].subsegments[0].file: game/longfilename is not a DOS 8.3 file name"
+        );
+    }
+
+    #[test]
+    fn test_library_signatures_name_functions_and_count_in_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // memcpy, then a function loading its address
+        let bytes = words_bytes(&[
+            0x2f86, 0x000b, 0x68f6, 0x0009, 0xd101, 0x000b, 0x0009, 0x0009, 0x0600, 0x0000,
+        ]);
+        std::fs::write(path("t.bin"), &bytes).unwrap();
+        std::fs::write(path("libc.sig"), "libc memcpy 2f86 000b 68f6 0009\n").unwrap();
+        std::fs::write(
+            path("c.yaml"),
+            format!(
+                r#"options:
+  target_path: {0}/t.bin
+  asm_path: {0}/asm
+  src_path: {0}/src
+  ld_scripts_path: {0}/build
+  syms_path: {0}/build
+  decomp_empty_funcs: false
+  signatures: [{0}/libc.sig]
+segments:
+  - name: main
+    type: code
+    start: 0
+    vram: 0x06000000
+    subsegments:
+      - {{start: 0, type: c, file: main, function_ranges: [[0, 7], [8, 0x13]]}}
+"#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        let (mut config, file_contents) = load_config(&path("c.yaml")).unwrap();
+        let (sections, _) = process_segments(&file_contents, &config);
+        let funcs = &sections[0].disassembled_funcs;
+
+        assert_eq!(funcs[&0].library.as_ref().unwrap().name, "memcpy");
+        assert!(funcs[&8].text.contains(".long _memcpy\n"));
+        let report = progress_report(&sections, &CIncludes::default(), &HashMap::new());
+        assert_eq!(report.progress.library_functions, 1);
+        assert!(format_progress(&report, ProgressFormat::Text, 0).contains(", 1 library"));
+
+        config.options.signatures.push(path("missing.sig"));
+        let problems = validate_config(&config, Some(&file_contents));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].to_string().contains("options.signatures[1]: "));
+    }
+
+    #[test]
+    fn test_library_signatures_match_function_starts_once_per_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // two copies of memcpy, then a function with the same bytes inside it
+        let bytes = words_bytes(&[
+            0x2f86, 0x000b, 0x68f6, 0x0009, 0x2f86, 0x000b, 0x68f6, 0x0009, 0xe000, 0x2f86, 0x000b,
+            0x68f6, 0x0009, 0x0009,
+        ]);
+        std::fs::write(path("t.bin"), &bytes).unwrap();
+        std::fs::write(path("libc.sig"), "libc memcpy 2f86 000b 68f6 0009\n").unwrap();
+        std::fs::write(
+            path("c.yaml"),
+            format!(
+                r#"options:
+  target_path: {0}/t.bin
+  asm_path: {0}/asm
+  src_path: {0}/src
+  ld_scripts_path: {0}/build
+  syms_path: {0}/build
+  decomp_empty_funcs: false
+  signatures: [{0}/libc.sig]
+segments:
+  - name: main
+    type: code
+    start: 0
+    vram: 0x06000000
+    subsegments:
+      - {{start: 0, type: c, file: main, function_ranges: [[0, 7], [8, 0xf], [0x10, 0x1b]]}}
+"#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        let (config, file_contents) = load_config(&path("c.yaml")).unwrap();
+        let (sections, _) = process_segments(&file_contents, &config);
+        let funcs = &sections[0].disassembled_funcs;
+
+        let library = |addr: u32| funcs[&addr].library.as_ref().map(|s| s.name.clone());
+        assert_eq!(library(0).as_deref(), Some("memcpy_06000000"));
+        assert_eq!(library(8).as_deref(), Some("memcpy_06000008"));
+        assert_eq!(library(0x10), None);
+        assert_eq!(funcs.len(), 3);
+    }
+
+    #[test]
+    fn test_extern_header_declares_functions_and_sized_data() {
+        let mut symbols_file = NamedTempFile::new().unwrap();
+        writeln!(symbols_file, "_header_magic = 0x06004000;").unwrap();
+        writeln!(
+            symbols_file,
+            "_fixture_sum = 0x06004020; // u32 fixture_sum(void)"
+        )
+        .unwrap();
+        let filename = symbols_file.path().to_str().unwrap();
+        let func = |addr: u32| DisassembledFunc {
+            addr,
+            end: addr + 0x10,
+            ..Default::default()
+        };
+        let sections = vec![
+            ProcessedSection {
+                segment: "main".to_string(),
+                labels: vec![
+                    DataSymbol {
+                        vram: 0x06004000,
+                        size: 4,
+                        name: "d_06004000".to_string(),
+                    },
+                    DataSymbol {
+                        vram: 0x06004004,
+                        size: 0xC,
+                        name: "D_06004004".to_string(),
+                    },
+                ],
+                ..Default::default()
+            },
+            ProcessedSection {
+                is_code: true,
+                segment: "main".to_string(),
+                disassembled_funcs: BTreeMap::from([(0x10, func(0x10)), (0x20, func(0x20))]),
+                vbase: 0x06004000,
+                ..Default::default()
+            },
+            ProcessedSection {
+                is_code: true,
+                segment: "overlay".to_string(),
+                disassembled_funcs: BTreeMap::from([(0, func(0))]),
+                vbase: 0x06080000,
+                ..Default::default()
+            },
+        ];
+
+        let header = gen_extern_header(
+            "main",
+            &sections,
+            &[(0x06010000, "_D_06010000".to_string())],
+            &read_user_symbols(filename),
+            &read_user_prototypes(filename),
+        );
+
+        assert_eq!(
+            header,
+            "/* Generated by rust-dis split, changes are overwritten. */
+#ifndef MAIN_SYMS_H
+#define MAIN_SYMS_H
+
+void func_06004010();
+u32 fixture_sum(void);
+
+extern unsigned char header_magic[0x4];
+extern unsigned char D_06004004[0xC];
+extern unsigned char D_06010000[];
+
+#endif
+"
+        );
+    }
 }
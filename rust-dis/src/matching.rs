@@ -0,0 +1,284 @@
+// Matching functions between two builds of the same game, e.g. the JP and US
+// releases. Functions are compared as instruction sequences with the fields
+// that move with the link address cleared, so a function matches its copy
+// wherever the copy and its literal pool were placed.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// Near matches sharing fewer instructions than this are not reported.
+const NEAR_MATCH_MINIMUM: f64 = 0.5;
+
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub from: u32,
+    pub to: u32,
+    pub confidence: f64,
+}
+
+// The instructions of a function with PC-relative displacements cleared:
+// literal loads, mova, branches and calls. Literal pool words inside the
+// function are cleared too, as they hold addresses that move with the link.
+pub fn normalize(bytes: &[u8]) -> Vec<u16> {
+    let mut words: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect();
+    let mut pool = Vec::new();
+    for (index, op) in words.iter_mut().enumerate() {
+        let disp = (*op & 0xff) as usize;
+        match *op >> 8 {
+            // mov.w @(disp,pc),rn
+            0x90..=0x9f => {
+                pool.push(index + 2 + disp);
+                *op &= 0xff00;
+            }
+            // mov.l @(disp,pc),rn
+            0xd0..=0xdf => {
+                let target = ((index * 2 + 4) & !3) / 2 + disp * 2;
+                pool.extend([target, target + 1]);
+                *op &= 0xff00;
+            }
+            // bra, bsr
+            0xa0..=0xbf => *op &= 0xf000,
+            // bt, bf, bt/s, bf/s, mova
+            0x89 | 0x8b | 0x8d | 0x8f | 0xc7 => *op &= 0xff00,
+            _ => {}
+        }
+    }
+    for index in pool {
+        if let Some(word) = words.get_mut(index) {
+            *word = 0;
+        }
+    }
+    words
+}
+
+// The fraction of instructions two functions of the same size share.
+fn similarity(a: &[u16], b: &[u16]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let same = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    same as f64 / a.len() as f64
+}
+
+// Pairs the functions of one build with those of another. A normalized
+// sequence found once in each build is a certain match. A sequence found n
+// times in both is paired in address order with confidence 1/n, and one found
+// a different number of times in each is left unmatched. The rest are paired
+// with the most similar function of the same size when each is the other's
+// best candidate.
+pub fn match_functions(from: &[(u32, Vec<u16>)], to: &[(u32, Vec<u16>)]) -> Vec<Match> {
+    let group = |functions: &[(u32, Vec<u16>)]| {
+        let mut groups = HashMap::<Vec<u16>, Vec<u32>>::new();
+        for (address, words) in functions {
+            groups.entry(words.clone()).or_default().push(*address);
+        }
+        groups
+    };
+    let (from_groups, to_groups) = (group(from), group(to));
+
+    let mut matches = Vec::new();
+    // copies that cannot be told apart, kept out of the near matches
+    let mut ambiguous = HashSet::new();
+    for (words, from_addresses) in &from_groups {
+        let Some(to_addresses) = to_groups.get(words) else {
+            continue;
+        };
+        if from_addresses.len() != to_addresses.len() {
+            ambiguous.extend(from_addresses.iter().map(|&address| (true, address)));
+            ambiguous.extend(to_addresses.iter().map(|&address| (false, address)));
+            continue;
+        }
+        let (mut from_addresses, mut to_addresses) = (from_addresses.clone(), to_addresses.clone());
+        from_addresses.sort();
+        to_addresses.sort();
+        for (&from, &to) in from_addresses.iter().zip(&to_addresses) {
+            matches.push(Match {
+                from,
+                to,
+                confidence: 1.0 / from_addresses.len() as f64,
+            });
+        }
+    }
+
+    let matched_from: HashSet<u32> = matches.iter().map(|m| m.from).collect();
+    let matched_to: HashSet<u32> = matches.iter().map(|m| m.to).collect();
+    let from_left: Vec<&(u32, Vec<u16>)> = from
+        .iter()
+        .filter(|(address, _)| !matched_from.contains(address))
+        .filter(|(address, _)| !ambiguous.contains(&(true, *address)))
+        .collect();
+    let to_left: Vec<&(u32, Vec<u16>)> = to
+        .iter()
+        .filter(|(address, _)| !matched_to.contains(address))
+        .filter(|(address, _)| !ambiguous.contains(&(false, *address)))
+        .collect();
+    let best = |function: &(u32, Vec<u16>), candidates: &[&(u32, Vec<u16>)]| {
+        candidates
+            .iter()
+            .map(|(address, words)| (*address, similarity(&function.1, words)))
+            .filter(|&(_, score)| score >= NEAR_MATCH_MINIMUM)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    };
+    for function in &from_left {
+        let Some((to, score)) = best(function, &to_left) else {
+            continue;
+        };
+        let to_function = to_left.iter().find(|(address, _)| *address == to).unwrap();
+        if best(to_function, &from_left).map(|(from, _)| from) == Some(function.0) {
+            matches.push(Match {
+                from: function.0,
+                to,
+                confidence: score,
+            });
+        }
+    }
+
+    matches.sort_by_key(|m| m.to);
+    matches
+}
+
+// Groups functions whose normalized instructions are identical, or of the same
+// size and at least min_similarity alike, as (index, similarity to the first
+// member). Functions without a copy are left out.
+pub fn clusters(functions: &[Vec<u16>], min_similarity: f64) -> Vec<Vec<(usize, f64)>> {
+    let mut clusters: Vec<Vec<(usize, f64)>> = Vec::new();
+    let mut identical = HashMap::<&[u16], usize>::new();
+    for (index, words) in functions.iter().enumerate() {
+        let cluster = identical.get(words.as_slice()).copied().or_else(|| {
+            clusters
+                .iter()
+                .position(|cluster| similarity(&functions[cluster[0].0], words) >= min_similarity)
+        });
+        match cluster {
+            Some(cluster) => {
+                let score = similarity(&functions[clusters[cluster][0].0], words);
+                clusters[cluster].push((index, score));
+                identical.insert(words, cluster);
+            }
+            None => {
+                identical.insert(words, clusters.len());
+                clusters.push(vec![(index, 1.0)]);
+            }
+        }
+    }
+    clusters.retain(|cluster| cluster.len() > 1);
+    clusters
+}
+
+// How alike two instruction sequences are, from 0 to 1: the Dice coefficient
+// of their n-grams, counting repeats. Unlike similarity, sequences of
+// different sizes compare, and inserted or reordered code costs only the
+// n-grams around it.
+pub fn ngram_similarity<T: Hash + Eq>(a: &[T], b: &[T], n: usize) -> f64 {
+    fn ngrams<T: Hash + Eq>(sequence: &[T], n: usize) -> HashMap<&[T], usize> {
+        let mut counts = HashMap::<&[T], usize>::new();
+        for window in sequence.windows(n.min(sequence.len()).max(1)) {
+            *counts.entry(window).or_default() += 1;
+        }
+        counts
+    }
+    let (a_counts, b_counts) = (ngrams(a, n), ngrams(b, n));
+    let total: usize = a_counts.values().chain(b_counts.values()).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let shared: usize = a_counts
+        .iter()
+        .map(|(ngram, count)| b_counts.get(ngram).map_or(0, |other| *other.min(count)))
+        .sum();
+    2.0 * shared as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_functions_match_across_builds_by_normalized_instructions() {
+        // the same function, its literal pool and callee moved
+        let strlen_jp = normalize(&[0x2f, 0xe6, 0xd1, 0x03, 0xb1, 0x20, 0x00, 0x0b, 0x6e, 0xf6]);
+        let strlen_us = normalize(&[0x2f, 0xe6, 0xd1, 0x05, 0xb3, 0x44, 0x00, 0x0b, 0x6e, 0xf6]);
+        // a different constant
+        let init_jp = normalize(&[0x2f, 0xe6, 0xe0, 0x10, 0x00, 0x0b, 0x6e, 0xf6]);
+        let init_us = normalize(&[0x2f, 0xe6, 0xe0, 0x20, 0x00, 0x0b, 0x6e, 0xf6]);
+        let unrelated = normalize(&[0x00, 0x09, 0x00, 0x09, 0x00, 0x09, 0x00, 0x0b]);
+
+        let matches = match_functions(
+            &[(0x06004000, strlen_jp), (0x06004100, init_jp)],
+            &[
+                (0x06004010, unrelated),
+                (0x06004200, strlen_us),
+                (0x06004300, init_us),
+            ],
+        );
+        assert_eq!(
+            matches,
+            [
+                Match {
+                    from: 0x06004000,
+                    to: 0x06004200,
+                    confidence: 1.0
+                },
+                Match {
+                    from: 0x06004100,
+                    to: 0x06004300,
+                    confidence: 0.75
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copies_found_a_different_number_of_times_are_not_guessed() {
+        let stub = normalize(&[0x00, 0x0b, 0x00, 0x09]);
+        let matches = match_functions(
+            &[(0x06004000, stub.clone()), (0x06004010, stub.clone())],
+            &[
+                (0x06004100, stub.clone()),
+                (0x06004110, stub.clone()),
+                (0x06004120, stub),
+            ],
+        );
+        assert_eq!(matches, []);
+    }
+
+    #[test]
+    fn test_copies_cluster_regardless_of_their_literal_pool() {
+        let functions = [
+            // mov.l with a pool word at a different address in each copy
+            normalize(&[
+                0xd1, 0x01, 0x00, 0x0b, 0x00, 0x09, 0x00, 0x09, 0x06, 0x00, 0x40, 0x00,
+            ]),
+            normalize(&[
+                0xd1, 0x01, 0x00, 0x0b, 0x00, 0x09, 0x00, 0x09, 0x06, 0x10, 0x40, 0x00,
+            ]),
+            normalize(&[
+                0xe1, 0x01, 0x00, 0x0b, 0x00, 0x09, 0x00, 0x09, 0x00, 0x09, 0x00, 0x09,
+            ]),
+            normalize(&[
+                0xe1, 0x02, 0x00, 0x0b, 0x00, 0x09, 0x00, 0x09, 0x00, 0x09, 0x00, 0x09,
+            ]),
+        ];
+        assert_eq!(clusters(&functions, 1.0), [vec![(0, 1.0), (1, 1.0)]]);
+        assert_eq!(
+            clusters(&functions, 0.8),
+            [vec![(0, 1.0), (1, 1.0)], vec![(2, 1.0), (3, 5.0 / 6.0)]]
+        );
+    }
+
+    #[test]
+    fn test_ngram_similarity_tolerates_inserted_instructions() {
+        let words = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<_>>();
+        let original = words("mov.l mov add jsr nop rts nop");
+        let patched = words("mov.l mov add add jsr nop rts nop");
+        let unrelated = words("sts.l tst bt bra nop");
+
+        assert_eq!(ngram_similarity(&original, &original, 3), 1.0);
+        // 4 of the 5 trigrams survive, against 6 in the patched copy
+        assert_eq!(ngram_similarity(&original, &patched, 3), 8.0 / 11.0);
+        assert_eq!(ngram_similarity(&original, &unrelated, 3), 0.0);
+    }
+}
@@ -0,0 +1,225 @@
+// Every name rust-dis generates comes from here, so C files, asm, symbol
+// files and linker scripts always agree. Templates are set from the config's
+// options.naming and use {vram:08X}-style placeholders.
+
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::cell::RefCell;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Naming {
+    // C name of a function
+    pub function: String,
+    // asm file of a function, also its INCLUDE_ASM id
+    pub function_file: String,
+    // C name of a data subsegment
+    pub data: String,
+    // asm file of a data subsegment
+    pub data_file: String,
+    // rodata moved into a function
+    pub rodata: String,
+    // literals pointing into bss
+    pub bss: String,
+    pub label: String,
+    pub data_label: String,
+    pub jump_table: String,
+    // prepended to a C name to get its asm symbol
+    pub symbol_prefix: String,
+    // keep generated and configured file names within DOS 8.3 limits
+    pub dos_file_names: bool,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Naming {
+            function: "func_{vram:08X}".to_string(),
+            function_file: "f{vram:07X}".to_string(),
+            data: "d_{vram:08X}".to_string(),
+            data_file: "d{vram:07X}".to_string(),
+            rodata: "D_{vram:08X}".to_string(),
+            bss: "D_{vram:08X}".to_string(),
+            label: ".L{vram:08X}".to_string(),
+            data_label: ".Ldat_{vram:08X}".to_string(),
+            jump_table: ".Ljtbl_{vram:08X}".to_string(),
+            symbol_prefix: "_".to_string(),
+            dos_file_names: false,
+        }
+    }
+}
+
+thread_local! {
+    static NAMING: RefCell<Naming> = RefCell::new(Naming::default());
+}
+
+pub fn set(naming: Naming) {
+    NAMING.with(|current| *current.borrow_mut() = naming);
+}
+
+fn placeholder() -> Regex {
+    Regex::new(r"\{([^}:]*)(?::([^}]*))?\}").unwrap()
+}
+
+fn expand(template: &str, vram: u32) -> String {
+    placeholder()
+        .replace_all(template, |caps: &regex::Captures| {
+            let spec = caps.get(2).map_or("", |spec| spec.as_str());
+            let width = spec
+                .trim_end_matches(['x', 'X'])
+                .trim_start_matches('0')
+                .parse()
+                .unwrap_or(0);
+            match spec.chars().last() {
+                Some('X') => format!("{:0width$X}", vram, width = width),
+                Some('x') => format!("{:0width$x}", vram, width = width),
+                _ => format!("{:0width$}", vram, width = width),
+            }
+        })
+        .into_owned()
+}
+
+fn with_template(vram: u32, template: impl Fn(&Naming) -> &String) -> String {
+    NAMING.with(|naming| expand(template(&naming.borrow()), vram))
+}
+
+pub fn function(vram: u32) -> String {
+    with_template(vram, |naming| &naming.function)
+}
+
+pub fn function_file(vram: u32) -> String {
+    with_template(vram, |naming| &naming.function_file)
+}
+
+pub fn data(vram: u32) -> String {
+    with_template(vram, |naming| &naming.data)
+}
+
+pub fn data_file(vram: u32) -> String {
+    with_template(vram, |naming| &naming.data_file)
+}
+
+pub fn rodata(vram: u32) -> String {
+    with_template(vram, |naming| &naming.rodata)
+}
+
+pub fn bss(vram: u32) -> String {
+    with_template(vram, |naming| &naming.bss)
+}
+
+pub fn label(vram: u32) -> String {
+    with_template(vram, |naming| &naming.label)
+}
+
+pub fn data_label(vram: u32) -> String {
+    with_template(vram, |naming| &naming.data_label)
+}
+
+pub fn jump_table(vram: u32) -> String {
+    with_template(vram, |naming| &naming.jump_table)
+}
+
+// The asm symbol of a C name.
+pub fn symbol(name: &str) -> String {
+    NAMING.with(|naming| format!("{}{}", naming.borrow().symbol_prefix, name))
+}
+
+// The C name of an asm symbol.
+pub fn c_name(symbol: &str) -> String {
+    NAMING.with(|naming| {
+        let naming = naming.borrow();
+        symbol
+            .strip_prefix(naming.symbol_prefix.as_str())
+            .unwrap_or(symbol)
+            .to_string()
+    })
+}
+
+// A file name stem the cygnus compiler can open from DOS.
+pub fn is_dos_name(stem: &str) -> bool {
+    !stem.is_empty()
+        && stem.len() <= 8
+        && stem
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// Problems with the templates as (field, message). highest_vram is the
+// largest address a name will be generated for.
+pub fn problems(naming: &Naming, highest_vram: u32) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let templates = [
+        ("function", &naming.function, false),
+        ("function_file", &naming.function_file, true),
+        ("data", &naming.data, false),
+        ("data_file", &naming.data_file, true),
+        ("rodata", &naming.rodata, false),
+        ("bss", &naming.bss, false),
+        ("label", &naming.label, false),
+        ("data_label", &naming.data_label, false),
+        ("jump_table", &naming.jump_table, false),
+    ];
+    let identifier = Regex::new(r"^[A-Za-z_.][A-Za-z0-9_.$]*$").unwrap();
+    for (field, template, is_file) in templates {
+        let placeholders: Vec<_> = placeholder().captures_iter(template).collect();
+        if placeholders.is_empty() {
+            problems.push((
+                field.to_string(),
+                format!("{:?} has no {{vram}} placeholder", template),
+            ));
+            continue;
+        }
+        if let Some(caps) = placeholders.iter().find(|caps| &caps[1] != "vram") {
+            problems.push((
+                field.to_string(),
+                format!("{:?}: unknown placeholder {}", template, &caps[0]),
+            ));
+            continue;
+        }
+        let name = expand(template, highest_vram);
+        if is_file && naming.dos_file_names && !is_dos_name(&name) {
+            problems.push((
+                field.to_string(),
+                format!("{} is not a DOS 8.3 file name", name),
+            ));
+        } else if !identifier.is_match(&name) {
+            problems.push((
+                field.to_string(),
+                format!("{} is not a valid symbol name", name),
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_expand_and_are_validated() {
+        let naming = Naming {
+            function: "fn_{vram:x}".to_string(),
+            function_file: "fn{vram:08X}".to_string(),
+            bss: "bss".to_string(),
+            dos_file_names: true,
+            ..Default::default()
+        };
+        assert_eq!(expand(&naming.function, 0x0600_4000), "fn_6004000");
+
+        let problems = problems(&naming, 0x0600_4000);
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    "function_file".to_string(),
+                    "fn06004000 is not a DOS 8.3 file name".to_string()
+                ),
+                (
+                    "bss".to_string(),
+                    "\"bss\" has no {vram} placeholder".to_string()
+                ),
+            ]
+        );
+        assert!(super::problems(&Naming::default(), 0x0600_4000).is_empty());
+    }
+}
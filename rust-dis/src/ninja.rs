@@ -0,0 +1,174 @@
+// build.ninja for a split: compile each C and asm file, link with the
+// generated script and verify the image against the target.
+
+use crate::linker::LinkerInput;
+use crate::Config;
+use std::collections::BTreeMap;
+
+fn ninja_escape(path: &str) -> String {
+    path.replace('$', "$$")
+        .replace(' ', "$ ")
+        .replace(':', "$:")
+}
+
+// Compile each linker input from src_path/<file>.c the way
+// tests/sh2-data/run.sh does, link with the generated script and verify the
+// result against the config, so ninja only rebuilds what changed.
+pub fn gen_build_ninja(
+    config: &Config,
+    inputs: &[LinkerInput],
+    ld_script: &str,
+    asm_deps: &BTreeMap<String, Vec<String>>,
+) -> String {
+    let options = &config.options;
+    let include_path = options.include_path.as_ref().unwrap_or(&options.src_path);
+    let obj = |name: &str| {
+        if options.obj_path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", options.obj_path.trim_end_matches('/'), name)
+        }
+    };
+    let segment_name = &config.segments.as_ref().unwrap()[0].name;
+
+    let mut ninja = format!(
+        "# Generated by rust-dis split from {}, changes are overwritten.\n\n",
+        config.filename
+    );
+    ninja.push_str(&format!("cc = {}\n", options.cc));
+    ninja.push_str("as = sh-elf-as\nld = sh-elf-ld\nobjcopy = sh-elf-objcopy\n");
+    ninja.push_str(&format!("rust_dis = {}\n", options.splitter));
+    ninja.push_str(&format!("includes = -I{}", ninja_escape(&options.src_path)));
+    if *include_path != options.src_path {
+        ninja.push_str(&format!(" -I{}", ninja_escape(include_path)));
+    }
+    ninja.push_str("\n\n");
+    ninja.push_str(
+        "rule cc
+  command = $cc -S $includes $in -o $out
+  description = CC $in
+rule as
+  command = $as -no-pad-sections $includes $in -o $out
+  description = AS $in
+rule coff_to_elf
+  command = $objcopy -Icoff-sh -Oelf32-sh $in $out
+rule ld
+  command = $ld --no-check-sections -nostdlib -T $script -o $out
+  description = LD $out
+rule bin
+  command = $objcopy -O binary $in $out
+rule verify
+  command = $rust_dis -q verify $config --build $in && touch $out
+  description = VERIFY $in
+
+",
+    );
+
+    let headers = format!(
+        "{} {}",
+        ninja_escape(&format!("{}/inc_asm.h", include_path)),
+        ninja_escape(&format!("{}/macro.inc", include_path))
+    );
+    let mut objects = Vec::new();
+    for input in inputs {
+        let file = input.object.trim_end_matches(".o");
+        let object = ninja_escape(&obj(&input.object));
+        if objects.contains(&object) {
+            continue;
+        }
+        let deps: String = asm_deps
+            .get(file)
+            .into_iter()
+            .flatten()
+            .map(|dep| format!(" {}", ninja_escape(dep)))
+            .collect();
+        let asm = ninja_escape(&obj(&format!("{}.s", file)));
+        let coff = ninja_escape(&obj(&format!("{}.cof", file)));
+        ninja.push_str(&format!(
+            "build {}: cc {} | {}{}\n",
+            asm,
+            ninja_escape(&format!("{}/{}.c", options.src_path, file)),
+            headers,
+            deps
+        ));
+        ninja.push_str(&format!("build {}: as {}\n", coff, asm));
+        ninja.push_str(&format!("build {}: coff_to_elf {}\n", object, coff));
+        objects.push(object);
+    }
+
+    let elf = ninja_escape(&obj(&format!("{}.elf", segment_name)));
+    let bin = ninja_escape(&obj(&format!("{}.bin", segment_name)));
+    let ok = ninja_escape(&obj(&format!("{}.ok", segment_name)));
+    ninja.push_str(&format!(
+        "\nbuild {}: ld | {} {}\n  script = {}\n",
+        elf,
+        objects.join(" "),
+        ninja_escape(ld_script),
+        ninja_escape(ld_script)
+    ));
+    ninja.push_str(&format!("build {}: bin {}\n", bin, elf));
+    ninja.push_str(&format!(
+        "build {}: verify {} | {}\n  config = {}\n\ndefault {}\n",
+        ok,
+        bin,
+        ninja_escape(&config.filename),
+        ninja_escape(&config.filename),
+        ok
+    ));
+    ninja
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linker::linker_inputs;
+
+    #[test]
+    fn test_build_ninja_compiles_each_object_and_verifies_the_link() {
+        let mut config: Config = serde_yaml::from_str(
+            r#"
+options:
+  target_path: game.bin
+  asm_path: asm
+  src_path: src
+  obj_path: build
+  ld_scripts_path: build
+  syms_path: build
+  decomp_empty_funcs: false
+  cc: sh2-gcc -O1
+segments:
+  - name: main
+    type: code
+    start: 0
+    vram: 0x06004000
+    subsegments:
+      - [0x0, c, main]
+      - [0x100, .data, my data]
+"#,
+        )
+        .unwrap();
+        config.filename = "game.yaml".to_string();
+        let inputs = linker_inputs(&config.segments.as_ref().unwrap()[0]);
+        let asm_deps = BTreeMap::from([(
+            "main".to_string(),
+            vec!["asm/f_nonmat/f6004000.s".to_string()],
+        )]);
+
+        let ninja = gen_build_ninja(&config, &inputs, "build/main.ld", &asm_deps);
+
+        assert!(ninja.contains("cc = sh2-gcc -O1\n"));
+        assert!(ninja.contains("rust_dis = rust-dis\n"));
+        assert!(ninja.contains(
+            "build build/main.s: cc src/main.c | src/inc_asm.h src/macro.inc asm/f_nonmat/f6004000.s\n\
+             build build/main.cof: as build/main.s\n\
+             build build/main.o: coff_to_elf build/main.cof\n"
+        ));
+        assert!(ninja
+            .contains("build build/my$ data.s: cc src/my$ data.c | src/inc_asm.h src/macro.inc\n"));
+        assert!(ninja.contains(
+            "build build/main.elf: ld | build/main.o build/my$ data.o build/main.ld\n  script = build/main.ld\n"
+        ));
+        assert!(ninja.contains("build build/main.ok: verify build/main.bin | game.yaml\n"));
+        assert!(ninja.ends_with("default build/main.ok\n"));
+    }
+}
@@ -0,0 +1,472 @@
+// Function bytes and relocated fields from SH object files: big-endian ELF32,
+// COFF-SH as sh-elf-as writes it before objcopy, and `ar` archives of either.
+
+use crate::signatures::Fixup;
+
+pub struct ObjectFunction {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    // offsets into bytes of fields the linker fills in
+    pub fixups: Vec<(usize, Fixup)>,
+}
+
+fn u8_at(data: &[u8], offset: usize) -> Result<u8, String> {
+    data.get(offset)
+        .copied()
+        .ok_or_else(|| format!("truncated at 0x{:X}", offset))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| format!("truncated at 0x{:X}", offset))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| format!("truncated at 0x{:X}", offset))
+}
+
+fn slice(data: &[u8], offset: usize, size: usize) -> Result<&[u8], String> {
+    data.get(offset..offset + size)
+        .ok_or_else(|| format!("truncated at 0x{:X}", offset))
+}
+
+fn c_string(data: &[u8], offset: usize) -> String {
+    let bytes = data.get(offset..).unwrap_or_default();
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+// Every function in an object or archive.
+pub fn read_functions(data: &[u8]) -> Result<Vec<ObjectFunction>, String> {
+    if data.starts_with(b"!<arch>\n") {
+        read_archive(data)
+    } else if data.starts_with(b"\x7fELF") {
+        read_elf(data)
+    } else if data.starts_with(&[0x05, 0x00]) {
+        read_coff(data)
+    } else {
+        Err("not an ELF or COFF-SH object or archive".to_string())
+    }
+}
+
+fn read_archive(data: &[u8]) -> Result<Vec<ObjectFunction>, String> {
+    let mut functions = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut offset = 8;
+    while offset + 60 <= data.len() {
+        let header = &data[offset..offset + 60];
+        let name = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .to_string();
+        let size: usize = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .map_err(|_| format!("bad archive member size at 0x{:X}", offset))?;
+        let mut member = slice(data, offset + 60, size)?;
+        offset += 60 + size + (size & 1);
+
+        let name = if name == "/" || name == "/SYM64/" || name == "__.SYMDEF" {
+            continue;
+        } else if name == "//" {
+            long_names = member;
+            continue;
+        } else if let Some(index) = name.strip_prefix('/') {
+            // GNU long name
+            let index: usize = index
+                .parse()
+                .map_err(|_| format!("bad member name {}", name))?;
+            c_string(long_names, index)
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        } else if let Some(length) = name.strip_prefix("#1/") {
+            // BSD long name, stored before the member
+            let length: usize = length
+                .parse()
+                .map_err(|_| format!("bad member name {}", name))?;
+            let long_name = c_string(slice(member, 0, length)?, 0);
+            member = &member[length..];
+            long_name
+        } else {
+            name.trim_end_matches('/').to_string()
+        };
+        let member_functions =
+            read_functions(member).map_err(|err| format!("{}: {}", name, err))?;
+        functions.extend(member_functions);
+    }
+    Ok(functions)
+}
+
+// Function symbols of one section as (name, start, size), sorted by start.
+// Symbols without a size end where the next one starts.
+fn function_extents(
+    mut symbols: Vec<(String, usize, usize)>,
+    section_size: usize,
+) -> Vec<(String, usize, usize)> {
+    symbols.sort_by_key(|&(_, start, _)| start);
+    symbols.dedup_by_key(|(_, start, _)| *start);
+    let starts: Vec<usize> = symbols.iter().map(|&(_, start, _)| start).collect();
+    for (index, symbol) in symbols.iter_mut().enumerate() {
+        if symbol.2 == 0 {
+            let next = starts.get(index + 1).copied().unwrap_or(section_size);
+            symbol.2 = next.saturating_sub(symbol.1);
+        }
+    }
+    symbols
+}
+
+fn read_elf(data: &[u8]) -> Result<Vec<ObjectFunction>, String> {
+    if data.get(4) != Some(&1) || data.get(5) != Some(&2) {
+        return Err("only big-endian ELF32 objects are supported".to_string());
+    }
+    let shoff = u32_at(data, 0x20)? as usize;
+    let shentsize = u16_at(data, 0x2e)? as usize;
+    let shnum = u16_at(data, 0x30)? as usize;
+    let shstrndx = u16_at(data, 0x32)? as usize;
+
+    struct Section {
+        name: usize,
+        kind: u32,
+        flags: u32,
+        offset: usize,
+        size: usize,
+        link: usize,
+        info: usize,
+    }
+    let mut sections = Vec::new();
+    for index in 0..shnum {
+        let header = shoff + index * shentsize;
+        sections.push(Section {
+            name: u32_at(data, header)? as usize,
+            kind: u32_at(data, header + 4)?,
+            flags: u32_at(data, header + 8)?,
+            offset: u32_at(data, header + 16)? as usize,
+            size: u32_at(data, header + 20)? as usize,
+            link: u32_at(data, header + 24)? as usize,
+            info: u32_at(data, header + 28)? as usize,
+        });
+    }
+    let section_names = sections.get(shstrndx).ok_or("no section name table")?;
+    let section_name = |section: &Section| c_string(data, section_names.offset + section.name);
+
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_RELA: u32 = 4;
+    const SHT_NOBITS: u32 = 8;
+    const SHT_REL: u32 = 9;
+    const SHF_EXECINSTR: u32 = 4;
+
+    let Some(symtab) = sections.iter().find(|section| section.kind == SHT_SYMTAB) else {
+        return Ok(Vec::new());
+    };
+    let strtab = sections.get(symtab.link).ok_or("bad symbol string table")?;
+    let mut symbols = Vec::new();
+    for entry in (symtab.offset..symtab.offset + symtab.size)
+        .step_by(16)
+        .skip(1)
+    {
+        let name = c_string(data, strtab.offset + u32_at(data, entry)? as usize);
+        let value = u32_at(data, entry + 4)? as usize;
+        let size = u32_at(data, entry + 8)? as usize;
+        let info = u8_at(data, entry + 12)?;
+        let shndx = u16_at(data, entry + 14)? as usize;
+        // STT_FUNC, or a global label from hand-written asm
+        let is_function = info & 0xf == 2 || (info & 0xf == 0 && info >> 4 == 1);
+        if is_function && !name.is_empty() {
+            symbols.push((shndx, name, value, size));
+        }
+    }
+
+    let mut functions = Vec::new();
+    for (index, section) in sections.iter().enumerate() {
+        if section.flags & SHF_EXECINSTR == 0 || section.kind == SHT_NOBITS {
+            continue;
+        }
+        let bytes = slice(data, section.offset, section.size)?;
+        let mut fixups = Vec::new();
+        for relocations in sections
+            .iter()
+            .filter(|other| matches!(other.kind, SHT_RELA | SHT_REL) && other.info == index)
+        {
+            let entry_size = if relocations.kind == SHT_RELA { 12 } else { 8 };
+            for entry in
+                (relocations.offset..relocations.offset + relocations.size).step_by(entry_size)
+            {
+                let offset = u32_at(data, entry)? as usize;
+                let fixup = match u32_at(data, entry + 4)? & 0xff {
+                    // R_SH_DIR32, R_SH_REL32
+                    1 | 2 => Fixup::Word32,
+                    // R_SH_IND12W
+                    4 => Fixup::Disp12,
+                    // R_SH_DIR8WPN, DIR8WPL, DIR8WPZ, DIR8BP, DIR8W, DIR8L
+                    3 | 5..=9 => Fixup::Disp8,
+                    _ => continue,
+                };
+                fixups.push((offset, fixup));
+            }
+        }
+        let section_symbols = symbols
+            .iter()
+            .filter(|symbol| symbol.0 == index)
+            .map(|(_, name, value, size)| (name.clone(), *value, *size))
+            .collect();
+        log::debug!("{}: {} bytes", section_name(section), section.size);
+        functions.extend(section_functions(bytes, section_symbols, &fixups));
+    }
+    Ok(functions)
+}
+
+fn read_coff(data: &[u8]) -> Result<Vec<ObjectFunction>, String> {
+    let nscns = u16_at(data, 2)? as usize;
+    let symptr = u32_at(data, 8)? as usize;
+    let nsyms = u32_at(data, 12)? as usize;
+    let opthdr = u16_at(data, 16)? as usize;
+    let strings = symptr + nsyms * 18;
+
+    const STYP_TEXT: u32 = 0x20;
+    const C_EXT: u8 = 2;
+    const C_STAT: u8 = 3;
+    // relocation types from binutils include/coff/sh.h
+    const R_SH_PCDISP8BY2: u16 = 10;
+    const R_SH_PCDISP: u16 = 12;
+    const R_SH_IMM32: u16 = 14;
+    const R_SH_PCRELIMM8BY2: u16 = 22;
+    const R_SH_PCRELIMM8BY4: u16 = 23;
+
+    let mut symbols = Vec::new();
+    let mut index = 0;
+    while index < nsyms {
+        let entry = symptr + index * 18;
+        let name = if u32_at(data, entry)? == 0 {
+            c_string(data, strings + u32_at(data, entry + 4)? as usize)
+        } else {
+            c_string(slice(data, entry, 8)?, 0)
+        };
+        let value = u32_at(data, entry + 8)? as usize;
+        let section = u16_at(data, entry + 12)? as i16;
+        let class = u8_at(data, entry + 16)?;
+        let aux = u8_at(data, entry + 17)? as usize;
+        // section symbols and local labels start with '.'
+        if section > 0 && matches!(class, C_EXT | C_STAT) && !name.starts_with('.') {
+            symbols.push((section as usize, name, value));
+        }
+        index += 1 + aux;
+    }
+
+    let mut functions = Vec::new();
+    for number in 1..=nscns {
+        let header = 20 + opthdr + (number - 1) * 40;
+        let vaddr = u32_at(data, header + 12)? as usize;
+        let size = u32_at(data, header + 16)? as usize;
+        let scnptr = u32_at(data, header + 20)? as usize;
+        let relptr = u32_at(data, header + 24)? as usize;
+        let nreloc = u16_at(data, header + 32)? as usize;
+        let flags = u32_at(data, header + 36)?;
+        if flags & STYP_TEXT == 0 {
+            continue;
+        }
+        let bytes = slice(data, scnptr, size)?;
+        let mut fixups = Vec::new();
+        for entry in (relptr..relptr + nreloc * 16).step_by(16) {
+            let offset = (u32_at(data, entry)? as usize).wrapping_sub(vaddr);
+            let fixup = match u16_at(data, entry + 12)? {
+                R_SH_IMM32 => Fixup::Word32,
+                // bra, bsr
+                R_SH_PCDISP => Fixup::Disp12,
+                // bt, bf, and mov.w, mov.l @(disp,pc)
+                R_SH_PCDISP8BY2 | R_SH_PCRELIMM8BY2 | R_SH_PCRELIMM8BY4 => Fixup::Disp8,
+                _ => continue,
+            };
+            fixups.push((offset, fixup));
+        }
+        let section_symbols = symbols
+            .iter()
+            .filter(|symbol| symbol.0 == number)
+            .map(|(_, name, value)| (name.clone(), value.wrapping_sub(vaddr), 0))
+            .collect();
+        functions.extend(section_functions(bytes, section_symbols, &fixups));
+    }
+    Ok(functions)
+}
+
+fn section_functions(
+    bytes: &[u8],
+    symbols: Vec<(String, usize, usize)>,
+    fixups: &[(usize, Fixup)],
+) -> Vec<ObjectFunction> {
+    function_extents(symbols, bytes.len())
+        .into_iter()
+        .filter(|&(_, start, size)| size > 0 && start + size <= bytes.len())
+        .map(|(name, start, size)| ObjectFunction {
+            name,
+            bytes: bytes[start..start + size].to_vec(),
+            fixups: fixups
+                .iter()
+                .filter(|(offset, _)| *offset >= start && *offset < start + size)
+                .map(|&(offset, fixup)| (offset - start, fixup))
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A relocatable ELF with one .text section, its symbols and relocations.
+    fn elf(text: &[u8], symbols: &[(&str, u32, u32)], relocations: &[(u32, u32)]) -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; 16];
+        for &(name, value, size) in symbols {
+            symtab.extend((strtab.len() as u32).to_be_bytes());
+            symtab.extend(value.to_be_bytes());
+            symtab.extend(size.to_be_bytes());
+            // global STT_FUNC in section 1
+            symtab.extend([0x12, 0, 0, 1]);
+            strtab.extend(name.bytes());
+            strtab.push(0);
+        }
+        let mut rela = Vec::new();
+        for &(offset, kind) in relocations {
+            rela.extend(offset.to_be_bytes());
+            rela.extend((1 << 8 | kind).to_be_bytes());
+            rela.extend(0u32.to_be_bytes());
+        }
+        let shstrtab = b"\0.text\0.symtab\0.strtab\0.rela.text\0.shstrtab\0".to_vec();
+        // (name, type, flags, contents, link, info)
+        let sections = [
+            (1, 1, 6, text, 0, 0),
+            (7, 2, 0, &symtab[..], 3, 1),
+            (15, 3, 0, &strtab[..], 0, 0),
+            (23, 4, 0, &rela[..], 2, 1),
+            (34, 3, 0, &shstrtab[..], 0, 0),
+        ];
+
+        let mut data = vec![0u8; 52];
+        data[..6].copy_from_slice(b"\x7fELF\x01\x02");
+        let mut headers = vec![0u8; 40];
+        for (name, kind, flags, contents, link, info) in sections {
+            let offset = data.len() as u32;
+            data.extend(contents);
+            for field in [
+                name,
+                kind,
+                flags,
+                0,
+                offset,
+                contents.len() as u32,
+                link,
+                info,
+                4,
+                0,
+            ] {
+                headers.extend(field.to_be_bytes());
+            }
+        }
+        let shoff = data.len() as u32;
+        data.extend(headers);
+        data[0x20..0x24].copy_from_slice(&shoff.to_be_bytes());
+        data[0x2e..0x30].copy_from_slice(&40u16.to_be_bytes());
+        data[0x30..0x32].copy_from_slice(&6u16.to_be_bytes());
+        data[0x32..0x34].copy_from_slice(&5u16.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_elf_functions_and_relocations_are_read_through_archives() {
+        let text = [
+            0xd1, 0x01, 0x41, 0x0b, 0x00, 0x09, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, // _caller
+            0x00, 0x0b, 0x00, 0x09, // _leaf, sized by the next symbol
+        ];
+        let object = elf(&text, &[("_caller", 0, 12), ("_leaf", 12, 0)], &[(8, 1)]);
+
+        let mut archive = b"!<arch>\n".to_vec();
+        archive.extend(format!("{:<16}{:<32}{:<10}`\n", "lib.o/", "", object.len()).bytes());
+        archive.extend(&object);
+
+        let functions = read_functions(&archive).unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].name, "_caller");
+        assert_eq!(functions[0].bytes, &text[..12]);
+        assert!(matches!(functions[0].fixups[..], [(8, Fixup::Word32)]));
+        assert_eq!(functions[1].name, "_leaf");
+        assert_eq!(functions[1].bytes, [0x00, 0x0b, 0x00, 0x09]);
+    }
+
+    #[test]
+    fn test_coff_functions_and_relocations_are_read() {
+        let text = words(&[
+            0xb000, 0x0009, 0xd101, 0x000b, 0x0009, 0x0009, 0x0000, 0x0000, // _func
+            0x000b, 0x0009, // _second_function, named from the string table
+        ]);
+        let mut relocations = Vec::new();
+        for (offset, kind) in [(0u32, 12u16), (4, 23), (12, 14)] {
+            relocations.extend(offset.to_be_bytes());
+            relocations.extend([0; 8]);
+            relocations.extend(kind.to_be_bytes());
+            relocations.extend([0; 2]);
+        }
+        let strings = b"_second_function\0";
+        let symbol = |name: [u8; 8], value: u32, class: u8, aux: u8| {
+            let mut entry = name.to_vec();
+            entry.extend(value.to_be_bytes());
+            entry.extend(1u16.to_be_bytes());
+            entry.extend([0, 0, class, aux]);
+            entry
+        };
+        let mut symbols = symbol(*b"_func\0\0\0", 0, 2, 0);
+        // a section symbol and its aux entry
+        symbols.extend(symbol(*b".text\0\0\0", 0, 3, 1));
+        symbols.extend([0; 18]);
+        symbols.extend(symbol([0, 0, 0, 0, 0, 0, 0, 4], 16, 2, 0));
+
+        let scnptr = 20 + 40;
+        let relptr = scnptr + text.len();
+        let symptr = relptr + relocations.len();
+        let mut data = Vec::new();
+        for field in [0x0500u16, 1] {
+            data.extend(field.to_be_bytes());
+        }
+        data.extend(0u32.to_be_bytes());
+        data.extend((symptr as u32).to_be_bytes());
+        data.extend(4u32.to_be_bytes());
+        data.extend([0; 4]);
+        data.extend(b".text\0\0\0");
+        for field in [0, 0, text.len(), scnptr, relptr, 0] {
+            data.extend((field as u32).to_be_bytes());
+        }
+        data.extend(3u16.to_be_bytes());
+        data.extend(0u16.to_be_bytes());
+        data.extend(0x20u32.to_be_bytes());
+        data.extend(&text);
+        data.extend(&relocations);
+        data.extend(&symbols);
+        data.extend((4 + strings.len() as u32).to_be_bytes());
+        data.extend(strings);
+
+        let functions = read_functions(&data).unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].name, "_func");
+        assert_eq!(functions[0].bytes, &text[..16]);
+        assert_eq!(
+            functions[0].fixups,
+            [(0, Fixup::Disp12), (4, Fixup::Disp8), (12, Fixup::Word32)]
+        );
+        assert_eq!(functions[1].name, "_second_function");
+        assert_eq!(functions[1].bytes, [0x00, 0x0b, 0x00, 0x09]);
+
+        // a symbol table cut short is an error, not a panic
+        let truncated = &data[..symptr + 17];
+        assert_eq!(
+            read_functions(truncated).err(),
+            Some(format!("truncated at 0x{:X}", symptr + 17))
+        );
+    }
+
+    fn words(words: &[u16]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+}
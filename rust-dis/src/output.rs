@@ -0,0 +1,204 @@
+// Writing a split's files, and the manifest of what the last split wrote.
+
+use crate::{Error, WithPath};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileChange {
+    Create,
+    Modify,
+    Remove,
+    Stale,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileChange::Create => write!(f, "create"),
+            FileChange::Modify => write!(f, "modify"),
+            FileChange::Remove => write!(f, "remove"),
+            FileChange::Stale => write!(f, "stale"),
+        }
+    }
+}
+
+// Every file a split produces goes through here, so files whose contents did
+// not change keep their timestamps and a dry run can list what would happen.
+#[derive(Default)]
+pub struct OutputPlan {
+    pub dry_run: bool,
+    pub written: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+    pub changes: Vec<(FileChange, String)>,
+    pub unchanged: usize,
+}
+
+impl OutputPlan {
+    pub fn create_dir(&self, path: &str) -> Result<(), Error> {
+        if self.dry_run {
+            return Ok(());
+        }
+        std::fs::create_dir_all(path).with_path(path)
+    }
+
+    pub fn write(&mut self, path: &str, contents: &str) -> Result<(), Error> {
+        self.written.insert(path.to_string());
+        let change = match std::fs::read(path) {
+            Ok(existing) if existing == contents.as_bytes() => {
+                self.unchanged += 1;
+                return Ok(());
+            }
+            Ok(_) => FileChange::Modify,
+            Err(_) => FileChange::Create,
+        };
+        if !self.dry_run {
+            std::fs::write(path, contents).with_path(path)?;
+        }
+        self.changes.push((change, path.to_string()));
+        Ok(())
+    }
+
+    // asm_path/manifest.txt lists the files the last split wrote there. Any
+    // of them this split did not write, e.g. a function that moved from
+    // f_nonmat to f_match or no longer exists, is removed.
+    pub fn update_manifest(&mut self, asm_path: &str) -> Result<(), Error> {
+        let manifest = format!("{}/manifest.txt", asm_path);
+        let previous = match std::fs::read_to_string(&manifest) {
+            Ok(previous) => previous,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error).with_path(&manifest),
+        };
+        for entry in previous.lines() {
+            let filename = format!("{}/{}", asm_path, entry);
+            if self.written.contains(&filename) || !Path::new(&filename).exists() {
+                continue;
+            }
+            if !self.dry_run {
+                std::fs::remove_file(&filename).with_path(&filename)?;
+            }
+            self.removed.insert(filename.clone());
+            self.changes.push((FileChange::Remove, filename));
+        }
+
+        let prefix = format!("{}/", asm_path);
+        let owned: String = self
+            .written
+            .iter()
+            .filter_map(|filename| filename.strip_prefix(&prefix))
+            .map(|entry| format!("{}\n", entry))
+            .collect();
+        self.write(&manifest, &owned)
+    }
+
+    // Report the .s files in a generated directory that this run did not
+    // produce and that no manifest accounts for.
+    pub fn find_stale(&mut self, dir: &str) -> Result<(), Error> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error).with_path(dir),
+        };
+        let mut stale = Vec::new();
+        for entry in entries {
+            let filename = format!(
+                "{}/{}",
+                dir,
+                entry.with_path(dir)?.file_name().to_string_lossy()
+            );
+            if filename.ends_with(".s")
+                && !self.written.contains(&filename)
+                && !self.removed.contains(&filename)
+            {
+                stale.push(filename);
+            }
+        }
+        stale.sort();
+        self.changes.extend(
+            stale
+                .into_iter()
+                .map(|filename| (FileChange::Stale, filename)),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_plan_only_writes_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let asm = format!("{}/f_match", dir.path().display());
+        std::fs::create_dir(&asm).unwrap();
+        let same = format!("{}/f6000000.s", asm);
+        let changed = format!("{}/f6000010.s", asm);
+        let moved = format!("{}/f6000020.s", asm);
+        std::fs::write(&same, "rts\n").unwrap();
+        std::fs::write(&changed, "nop\n").unwrap();
+        std::fs::write(&moved, "nop\n").unwrap();
+        let new = format!("{}/f6000030.s", asm);
+
+        let mut plan = OutputPlan {
+            dry_run: true,
+            ..Default::default()
+        };
+        plan.write(&same, "rts\n").unwrap();
+        plan.write(&changed, "rts\n").unwrap();
+        plan.write(&new, "rts\n").unwrap();
+        plan.find_stale(&asm).unwrap();
+
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(
+            plan.changes,
+            vec![
+                (FileChange::Modify, changed.clone()),
+                (FileChange::Create, new.clone()),
+                (FileChange::Stale, moved),
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(&changed).unwrap(), "nop\n");
+        assert!(!Path::new(&new).exists());
+
+        plan.dry_run = false;
+        plan.write(&new, "rts\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&new).unwrap(), "rts\n");
+    }
+
+    #[test]
+    fn test_manifest_removes_files_no_longer_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let asm = dir.path().display().to_string();
+        std::fs::create_dir(format!("{}/f_nonmat", asm)).unwrap();
+        std::fs::create_dir(format!("{}/f_match", asm)).unwrap();
+        let nonmat = format!("{}/f_nonmat/f6000000.s", asm);
+        let matched = format!("{}/f_match/f6000000.s", asm);
+        let handmade = format!("{}/f_match/notes.s", asm);
+        std::fs::write(&handmade, "").unwrap();
+
+        let mut plan = OutputPlan::default();
+        plan.write(&nonmat, "rts\n").unwrap();
+        plan.update_manifest(&asm).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(format!("{}/manifest.txt", asm)).unwrap(),
+            "f_nonmat/f6000000.s\n"
+        );
+
+        // the function was decompiled, so it moved to f_match
+        let mut plan = OutputPlan::default();
+        plan.write(&matched, "rts\n").unwrap();
+        plan.update_manifest(&asm).unwrap();
+        plan.find_stale(&format!("{}/f_nonmat", asm)).unwrap();
+        plan.find_stale(&format!("{}/f_match", asm)).unwrap();
+
+        assert!(!Path::new(&nonmat).exists());
+        assert!(plan.changes.contains(&(FileChange::Remove, nonmat)));
+        assert!(plan
+            .changes
+            .contains(&(FileChange::Stale, handmade.clone())));
+        assert!(Path::new(&handmade).exists());
+    }
+}
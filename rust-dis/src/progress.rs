@@ -0,0 +1,476 @@
+// How much of a target is decompiled: functions still included as asm count
+// as remaining, library functions and exact copies of decompiled functions in
+// other targets do not.
+
+use crate::{
+    find_includes, matching, naming, parse_yaml2, process_segments, serialize_address, CIncludes,
+    Config, Error, ProcessedSection, WithPath,
+};
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ProgressFormat {
+    /// A line per segment and file
+    Text,
+    /// Every segment, file and function
+    Json,
+    /// timestamp,matched_bytes,total_bytes,matched_functions,total_functions
+    Csv,
+}
+
+#[derive(Default, Serialize)]
+pub struct Progress {
+    pub matched_bytes: u64,
+    pub total_bytes: u64,
+    pub matched_functions: usize,
+    pub non_matching_functions: usize,
+    pub total_functions: usize,
+    // functions matched by a library signature
+    pub library_bytes: u64,
+    pub library_functions: usize,
+}
+
+impl Progress {
+    fn add(&mut self, size: u64, matched: bool, non_matching: bool, library: bool) {
+        self.total_bytes += size;
+        self.total_functions += 1;
+        if matched {
+            self.matched_bytes += size;
+            self.matched_functions += 1;
+        }
+        if non_matching {
+            self.non_matching_functions += 1;
+        }
+        if library {
+            self.library_bytes += size;
+            self.library_functions += 1;
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.matched_bytes as f64 * 100.0 / self.total_bytes as f64
+        };
+        write!(
+            f,
+            "0x{:X}/0x{:X} bytes ({:.2}%), {}/{} functions, {} non-matching",
+            self.matched_bytes,
+            self.total_bytes,
+            percent,
+            self.matched_functions,
+            self.total_functions,
+            self.non_matching_functions
+        )?;
+        if self.library_functions > 0 {
+            write!(
+                f,
+                ", {} library (0x{:X} bytes)",
+                self.library_functions, self.library_bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct FunctionProgress {
+    pub name: String,
+    #[serde(serialize_with = "serialize_address")]
+    pub address: u32,
+    pub size: u32,
+    pub matched: bool,
+    pub non_matching: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library: Option<String>,
+    // the decompiled copy this counts as matched through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_of: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct FileProgress {
+    pub name: String,
+    #[serde(flatten)]
+    pub progress: Progress,
+    pub functions: Vec<FunctionProgress>,
+}
+
+#[derive(Serialize)]
+pub struct SegmentProgress {
+    pub name: String,
+    #[serde(flatten)]
+    pub progress: Progress,
+    pub files: Vec<FileProgress>,
+}
+
+#[derive(Default, Serialize)]
+pub struct ProgressReport {
+    #[serde(flatten)]
+    pub progress: Progress,
+    pub segments: Vec<SegmentProgress>,
+}
+
+// A function counts as matched under the same rule split uses to put it in
+// f_match: no C file INCLUDE_ASMs it any more. Non-matching functions have C
+// behind NON_MATCHING but still build from asm.
+pub fn progress_report(
+    processed_sections: &[ProcessedSection],
+    includes: &CIncludes,
+    copies: &HashMap<u32, String>,
+) -> ProgressReport {
+    let mut report = ProgressReport::default();
+    for section in processed_sections.iter().filter(|section| section.is_code) {
+        if report.segments.last().map(|segment| &segment.name) != Some(&section.segment) {
+            report.segments.push(SegmentProgress {
+                name: section.segment.clone(),
+                progress: Progress::default(),
+                files: Vec::new(),
+            });
+        }
+        let segment = report.segments.last_mut().unwrap();
+        if segment.files.last().map(|file| &file.name) != Some(&section.file) {
+            segment.files.push(FileProgress {
+                name: section.file.clone(),
+                progress: Progress::default(),
+                functions: Vec::new(),
+            });
+        }
+        let file = segment.files.last_mut().unwrap();
+
+        for func in section.disassembled_funcs.values() {
+            let address = func.addr + section.vbase as u32;
+            let name = naming::function(address);
+            let asm_id = naming::function_file(address);
+            let matched = !includes.contains(&name) && !includes.contains(&asm_id);
+            // a decompiled copy elsewhere counts for this one too
+            let copy_of = copies.get(&address).filter(|_| !matched).cloned();
+            let matched = matched || copy_of.is_some();
+            let non_matching =
+                includes.non_matching.contains(&name) || includes.non_matching.contains(&asm_id);
+            let size = func.end - func.addr;
+            let library = func.library.is_some();
+            report
+                .progress
+                .add(size as u64, matched, non_matching, library);
+            segment
+                .progress
+                .add(size as u64, matched, non_matching, library);
+            file.progress
+                .add(size as u64, matched, non_matching, library);
+            file.functions.push(FunctionProgress {
+                name,
+                address,
+                size,
+                matched,
+                non_matching,
+                library: func
+                    .library
+                    .as_ref()
+                    .map(|signature| signature.library.clone()),
+                copy_of,
+            });
+        }
+    }
+    report
+}
+
+pub fn format_progress(report: &ProgressReport, format: ProgressFormat, timestamp: u64) -> String {
+    match format {
+        ProgressFormat::Text => {
+            let mut output = String::new();
+            for segment in &report.segments {
+                output.push_str(&format!("{}: {}\n", segment.name, segment.progress));
+                for file in &segment.files {
+                    output.push_str(&format!("  {}: {}\n", file.name, file.progress));
+                }
+            }
+            output.push_str(&format!("total: {}\n", report.progress));
+            output
+        }
+        ProgressFormat::Json => format!("{}\n", serde_json::to_string_pretty(report).unwrap()),
+        ProgressFormat::Csv => format!(
+            "{},{},{},{},{}\n",
+            timestamp,
+            report.progress.matched_bytes,
+            report.progress.total_bytes,
+            report.progress.matched_functions,
+            report.progress.total_functions
+        ),
+    }
+}
+
+// One copy of a function shared between targets, as listed in the mapping
+// written by `duplicates`. config is canonical so the mapping works from any
+// directory, and function and asm are its C name and asm id under its own
+// config's naming. similarity is to the group's first copy; only exact
+// copies share progress.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SharedCopy {
+    pub config: String,
+    pub segment: String,
+    pub address: u32,
+    pub function: String,
+    pub asm: String,
+    pub similarity: f64,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct SharedFunction {
+    pub name: String,
+    pub copies: Vec<SharedCopy>,
+}
+
+// Every function of a config at least min_size bytes long, normalized to
+// find its copies.
+pub fn shared_copies(
+    config: &Config,
+    file_contents: &Vec<u8>,
+    min_size: u32,
+) -> Vec<(SharedCopy, Vec<u16>)> {
+    let (processed_sections, _) = process_segments(file_contents, config);
+    let mut copies = Vec::new();
+    for section in processed_sections.iter().filter(|section| section.is_code) {
+        for func in section.disassembled_funcs.values() {
+            if func.data || func.end - func.addr < min_size {
+                continue;
+            }
+            let address = func.addr + section.vbase as u32;
+            let Some(bytes) = file_contents.get(func.addr as usize..func.end as usize) else {
+                continue;
+            };
+            let copy = SharedCopy {
+                config: fs::canonicalize(&config.filename).map_or_else(
+                    |_| config.filename.clone(),
+                    |path| path.display().to_string(),
+                ),
+                segment: section.segment.clone(),
+                address,
+                function: naming::function(address),
+                asm: naming::function_file(address),
+                similarity: 1.0,
+            };
+            copies.push((copy, matching::normalize(bytes)));
+        }
+    }
+    copies
+}
+
+// The mapping `progress --shared` reads, named after each group's first copy.
+pub fn format_shared_functions(copies: &[SharedCopy], clusters: &[Vec<(usize, f64)>]) -> String {
+    let shared: Vec<SharedFunction> = clusters
+        .iter()
+        .map(|cluster| SharedFunction {
+            name: copies[cluster[0].0].function.clone(),
+            copies: cluster
+                .iter()
+                .map(|&(index, similarity)| SharedCopy {
+                    similarity,
+                    ..copies[index].clone()
+                })
+                .collect(),
+        })
+        .collect();
+    format!(
+        "# functions copied between targets, see `progress --shared`\n{}",
+        serde_yaml::to_string(&shared).unwrap()
+    )
+}
+
+// Functions of config that count as decompiled because a copy listed in the
+// mapping at path is, by address, with the copy that was.
+pub fn decompiled_copies(config: &Config, path: &str) -> Result<HashMap<u32, String>, Error> {
+    let contents = std::fs::read_to_string(path).with_path(path)?;
+    let shared: Vec<SharedFunction> = serde_yaml::from_str(&contents)
+        .map_err(|error| Error::Analysis(format!("{}: {}", path, error)))?;
+    let canonical =
+        |filename: &str| fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename));
+    let this_config = canonical(&config.filename);
+
+    let mut includes = HashMap::<PathBuf, CIncludes>::new();
+    let mut copies = HashMap::new();
+    for function in &shared {
+        let mut decompiled = None;
+        // copies that differ may not be decompiled by the same C
+        let exact: Vec<&SharedCopy> = function
+            .copies
+            .iter()
+            .filter(|copy| copy.similarity >= 1.0)
+            .collect();
+        for &copy in &exact {
+            let copy_config = canonical(&copy.config);
+            if !includes.contains_key(&copy_config) {
+                let copy_includes = if copy_config == this_config {
+                    find_includes(config)
+                } else {
+                    let other =
+                        parse_yaml2(copy.config.clone()).map_err(|problem| Error::Config {
+                            filename: copy.config.clone(),
+                            problems: vec![problem],
+                        })?;
+                    find_includes(&other)
+                };
+                includes.insert(copy_config.clone(), copy_includes);
+            }
+            let copy_includes = &includes[&copy_config];
+            if !copy_includes.contains(&copy.function) && !copy_includes.contains(&copy.asm) {
+                decompiled = Some(copy);
+                break;
+            }
+        }
+        let Some(decompiled) = decompiled else {
+            continue;
+        };
+        for copy in exact {
+            if canonical(&copy.config) == this_config && copy != decompiled {
+                copies.insert(
+                    copy.address,
+                    format!("{} {}", decompiled.config, decompiled.function),
+                );
+            }
+        }
+    }
+    Ok(copies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::words_bytes;
+    use crate::{load_config, DisassembledFunc};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_progress_counts_include_asm_as_remaining() {
+        let func = |addr: u32, end: u32| DisassembledFunc {
+            addr,
+            end,
+            name: format!("f{:07X}", addr + 0x06000000),
+            ..Default::default()
+        };
+        let section = |file: &str, funcs: Vec<DisassembledFunc>| ProcessedSection {
+            is_code: true,
+            segment: "main".to_string(),
+            file: file.to_string(),
+            disassembled_funcs: funcs.into_iter().map(|f| (f.addr, f)).collect(),
+            vbase: 0x06000000,
+            ..Default::default()
+        };
+        let sections = vec![
+            section("main", vec![func(0, 0x10), func(0x10, 0x30)]),
+            ProcessedSection::default(),
+            section("libc", vec![func(0x30, 0x38)]),
+        ];
+        let includes = CIncludes {
+            asm_only: HashSet::from(["func_06000030".to_string()]),
+            non_matching: HashSet::from(["f6000010".to_string()]),
+        };
+
+        let report = progress_report(&sections, &includes, &HashMap::new());
+
+        assert_eq!(report.segments.len(), 1);
+        assert_eq!(report.segments[0].files.len(), 2);
+        assert_eq!(
+            format_progress(&report, ProgressFormat::Text, 0),
+            "main: 0x10/0x38 bytes (28.57%), 1/3 functions, 1 non-matching\n  main: 0x10/0x30 bytes (33.33%), 1/2 functions, 1 non-matching\n  libc: 0x0/0x8 bytes (0.00%), 0/1 functions, 0 non-matching\ntotal: 0x10/0x38 bytes (28.57%), 1/3 functions, 1 non-matching\n"
+        );
+        assert_eq!(
+            format_progress(&report, ProgressFormat::Csv, 1700000000),
+            "1700000000,16,56,1,3\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_progress(&report, ProgressFormat::Json, 0)).unwrap();
+        assert_eq!(json["matched_bytes"], 16);
+        assert_eq!(
+            json["segments"][0]["files"][0]["functions"][1]["address"],
+            "0x06000010"
+        );
+        assert_eq!(
+            json["segments"][0]["files"][0]["functions"][1]["matched"],
+            false
+        );
+    }
+
+    #[test]
+    fn test_copies_in_other_targets_count_as_decompiled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // the same function in two overlays, its literal pointing into each one
+        for (name, vram) in [("a", 0x06000000u32), ("b", 0x06100000)] {
+            let bytes = words_bytes(&[0xd101, 0x000b, 0x0009, 0x0009, (vram >> 16) as u16, 0x0000]);
+            std::fs::write(path(&format!("{}.bin", name)), &bytes).unwrap();
+            std::fs::write(
+                path(&format!("{}.yaml", name)),
+                format!(
+                    r#"options:
+  target_path: {0}/{1}.bin
+  asm_path: {0}/{1}/asm
+  src_path: {0}/{1}/src
+  ld_scripts_path: {0}/{1}/build
+  syms_path: {0}/{1}/build
+  decomp_empty_funcs: false
+segments:
+  - name: {1}
+    type: code
+    start: 0
+    vram: 0x{2:08X}
+    subsegments:
+      - {{start: 0, type: c, file: main, function_ranges: [[0, 0xb]]}}
+"#,
+                    dir.path().display(),
+                    name,
+                    vram
+                ),
+            )
+            .unwrap();
+        }
+        // only a's copy has been decompiled
+        std::fs::create_dir_all(path("b/src")).unwrap();
+        std::fs::write(
+            path("b/src/main.c"),
+            "INCLUDE_ASM(\"asm\", f6100000, func_06100000);\n",
+        )
+        .unwrap();
+
+        let mut copies = Vec::new();
+        let mut words = Vec::new();
+        for name in ["a", "b"] {
+            let (config, file_contents) = load_config(&path(&format!("{}.yaml", name))).unwrap();
+            for (copy, normalized) in shared_copies(&config, &file_contents, 8) {
+                copies.push(copy);
+                words.push(normalized);
+            }
+        }
+        let clusters = matching::clusters(&words, 1.0);
+        assert_eq!(clusters, [vec![(0, 1.0), (1, 1.0)]]);
+        let mapping = format_shared_functions(&copies, &clusters);
+        std::fs::write(path("shared.yaml"), &mapping).unwrap();
+
+        let (config, file_contents) = load_config(&path("b.yaml")).unwrap();
+        let copies = decompiled_copies(&config, &path("shared.yaml")).unwrap();
+        let a_config = fs::canonicalize(path("a.yaml")).unwrap();
+        assert_eq!(
+            copies[&0x06100000],
+            format!("{} func_06000000", a_config.display())
+        );
+        let (sections, _) = process_segments(&file_contents, &config);
+        let report = progress_report(&sections, &find_includes(&config), &copies);
+        assert_eq!(report.progress.matched_functions, 1);
+
+        // a near copy does not share progress
+        let mut shared: Vec<SharedFunction> = serde_yaml::from_str(&mapping).unwrap();
+        shared[0].copies[1].similarity = 0.9;
+        std::fs::write(path("shared.yaml"), serde_yaml::to_string(&shared).unwrap()).unwrap();
+        assert!(decompiled_copies(&config, &path("shared.yaml"))
+            .unwrap()
+            .is_empty());
+    }
+}
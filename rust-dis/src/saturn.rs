@@ -0,0 +1,440 @@
+// The Saturn memory map: SH-2 on-chip modules, SCU, VDP1, VDP2, SMPC and
+// SCSP registers and the BIOS routine pointers by address. Literals that hold
+// one of these addresses are printed by name, and split writes saturn_regs.h
+// with a #define for each.
+
+const REGISTERS: &[(u32, &str)] = &[
+    // SMPC, registers sit on odd addresses
+    (0x2010_0000, "SMPC_BASE"),
+    (0x2010_0001, "SMPC_IREG0"),
+    (0x2010_0003, "SMPC_IREG1"),
+    (0x2010_0005, "SMPC_IREG2"),
+    (0x2010_0007, "SMPC_IREG3"),
+    (0x2010_0009, "SMPC_IREG4"),
+    (0x2010_000B, "SMPC_IREG5"),
+    (0x2010_000D, "SMPC_IREG6"),
+    (0x2010_001F, "SMPC_COMREG"),
+    (0x2010_0021, "SMPC_OREG0"),
+    (0x2010_0023, "SMPC_OREG1"),
+    (0x2010_0025, "SMPC_OREG2"),
+    (0x2010_0027, "SMPC_OREG3"),
+    (0x2010_0029, "SMPC_OREG4"),
+    (0x2010_002B, "SMPC_OREG5"),
+    (0x2010_002D, "SMPC_OREG6"),
+    (0x2010_002F, "SMPC_OREG7"),
+    (0x2010_0031, "SMPC_OREG8"),
+    (0x2010_0033, "SMPC_OREG9"),
+    (0x2010_0035, "SMPC_OREG10"),
+    (0x2010_0037, "SMPC_OREG11"),
+    (0x2010_0039, "SMPC_OREG12"),
+    (0x2010_003B, "SMPC_OREG13"),
+    (0x2010_003D, "SMPC_OREG14"),
+    (0x2010_003F, "SMPC_OREG15"),
+    (0x2010_0041, "SMPC_OREG16"),
+    (0x2010_0043, "SMPC_OREG17"),
+    (0x2010_0045, "SMPC_OREG18"),
+    (0x2010_0047, "SMPC_OREG19"),
+    (0x2010_0049, "SMPC_OREG20"),
+    (0x2010_004B, "SMPC_OREG21"),
+    (0x2010_004D, "SMPC_OREG22"),
+    (0x2010_004F, "SMPC_OREG23"),
+    (0x2010_0051, "SMPC_OREG24"),
+    (0x2010_0053, "SMPC_OREG25"),
+    (0x2010_0055, "SMPC_OREG26"),
+    (0x2010_0057, "SMPC_OREG27"),
+    (0x2010_0059, "SMPC_OREG28"),
+    (0x2010_005B, "SMPC_OREG29"),
+    (0x2010_005D, "SMPC_OREG30"),
+    (0x2010_005F, "SMPC_OREG31"),
+    (0x2010_0061, "SMPC_SR"),
+    (0x2010_0063, "SMPC_SF"),
+    (0x2010_0075, "SMPC_PDR1"),
+    (0x2010_0077, "SMPC_PDR2"),
+    (0x2010_0079, "SMPC_DDR1"),
+    (0x2010_007B, "SMPC_DDR2"),
+    (0x2010_007D, "SMPC_IOSEL"),
+    (0x2010_007F, "SMPC_EXLE"),
+    // SCSP
+    (0x25A0_0000, "SCSP_SOUND_RAM"),
+    (0x25B0_0000, "SCSP_SLOTS"),
+    (0x25B0_0400, "SCSP_MVOL"),
+    (0x25B0_0402, "SCSP_RBL"),
+    (0x25B0_0404, "SCSP_MIBUF"),
+    (0x25B0_0406, "SCSP_MOBUF"),
+    (0x25B0_0408, "SCSP_MSLC"),
+    (0x25B0_0412, "SCSP_DMEAL"),
+    (0x25B0_0414, "SCSP_DMEAH"),
+    (0x25B0_0416, "SCSP_DTLG"),
+    (0x25B0_0418, "SCSP_TIMA"),
+    (0x25B0_041A, "SCSP_TIMB"),
+    (0x25B0_041C, "SCSP_TIMC"),
+    (0x25B0_041E, "SCSP_SCIEB"),
+    (0x25B0_0420, "SCSP_SCIPD"),
+    (0x25B0_0422, "SCSP_SCIRE"),
+    (0x25B0_0424, "SCSP_SCILV0"),
+    (0x25B0_0426, "SCSP_SCILV1"),
+    (0x25B0_0428, "SCSP_SCILV2"),
+    (0x25B0_042A, "SCSP_MCIEB"),
+    (0x25B0_042C, "SCSP_MCIPD"),
+    (0x25B0_042E, "SCSP_MCIRE"),
+    // VDP1
+    (0x25C0_0000, "VDP1_VRAM"),
+    (0x25C8_0000, "VDP1_FB"),
+    (0x25D0_0000, "VDP1_TVMR"),
+    (0x25D0_0002, "VDP1_FBCR"),
+    (0x25D0_0004, "VDP1_PTMR"),
+    (0x25D0_0006, "VDP1_EWDR"),
+    (0x25D0_0008, "VDP1_EWLR"),
+    (0x25D0_000A, "VDP1_EWRR"),
+    (0x25D0_000C, "VDP1_ENDR"),
+    (0x25D0_0010, "VDP1_EDSR"),
+    (0x25D0_0012, "VDP1_LOPR"),
+    (0x25D0_0014, "VDP1_COPR"),
+    (0x25D0_0016, "VDP1_MODR"),
+    // VDP2
+    (0x25E0_0000, "VDP2_VRAM"),
+    (0x25F0_0000, "VDP2_CRAM"),
+    (0x25F8_0000, "VDP2_TVMD"),
+    (0x25F8_0002, "VDP2_EXTEN"),
+    (0x25F8_0004, "VDP2_TVSTAT"),
+    (0x25F8_0006, "VDP2_VRSIZE"),
+    (0x25F8_0008, "VDP2_HCNT"),
+    (0x25F8_000A, "VDP2_VCNT"),
+    (0x25F8_000E, "VDP2_RAMCTL"),
+    (0x25F8_0010, "VDP2_CYCA0L"),
+    (0x25F8_0012, "VDP2_CYCA0U"),
+    (0x25F8_0014, "VDP2_CYCA1L"),
+    (0x25F8_0016, "VDP2_CYCA1U"),
+    (0x25F8_0018, "VDP2_CYCB0L"),
+    (0x25F8_001A, "VDP2_CYCB0U"),
+    (0x25F8_001C, "VDP2_CYCB1L"),
+    (0x25F8_001E, "VDP2_CYCB1U"),
+    (0x25F8_0020, "VDP2_BGON"),
+    (0x25F8_0022, "VDP2_MZCTL"),
+    (0x25F8_0024, "VDP2_SFSEL"),
+    (0x25F8_0026, "VDP2_SFCODE"),
+    (0x25F8_0028, "VDP2_CHCTLA"),
+    (0x25F8_002A, "VDP2_CHCTLB"),
+    (0x25F8_002C, "VDP2_BMPNA"),
+    (0x25F8_002E, "VDP2_BMPNB"),
+    (0x25F8_0030, "VDP2_PNCN0"),
+    (0x25F8_0032, "VDP2_PNCN1"),
+    (0x25F8_0034, "VDP2_PNCN2"),
+    (0x25F8_0036, "VDP2_PNCN3"),
+    (0x25F8_0038, "VDP2_PNCR"),
+    (0x25F8_003A, "VDP2_PLSZ"),
+    (0x25F8_003C, "VDP2_MPOFN"),
+    (0x25F8_003E, "VDP2_MPOFR"),
+    (0x25F8_0040, "VDP2_MPABN0"),
+    (0x25F8_0042, "VDP2_MPCDN0"),
+    (0x25F8_0044, "VDP2_MPABN1"),
+    (0x25F8_0046, "VDP2_MPCDN1"),
+    (0x25F8_0048, "VDP2_MPABN2"),
+    (0x25F8_004A, "VDP2_MPCDN2"),
+    (0x25F8_004C, "VDP2_MPABN3"),
+    (0x25F8_004E, "VDP2_MPCDN3"),
+    (0x25F8_0050, "VDP2_MPABRA"),
+    (0x25F8_0052, "VDP2_MPCDRA"),
+    (0x25F8_0054, "VDP2_MPEFRA"),
+    (0x25F8_0056, "VDP2_MPGHRA"),
+    (0x25F8_0058, "VDP2_MPIJRA"),
+    (0x25F8_005A, "VDP2_MPKLRA"),
+    (0x25F8_005C, "VDP2_MPMNRA"),
+    (0x25F8_005E, "VDP2_MPOPRA"),
+    (0x25F8_0060, "VDP2_MPABRB"),
+    (0x25F8_0062, "VDP2_MPCDRB"),
+    (0x25F8_0064, "VDP2_MPEFRB"),
+    (0x25F8_0066, "VDP2_MPGHRB"),
+    (0x25F8_0068, "VDP2_MPIJRB"),
+    (0x25F8_006A, "VDP2_MPKLRB"),
+    (0x25F8_006C, "VDP2_MPMNRB"),
+    (0x25F8_006E, "VDP2_MPOPRB"),
+    (0x25F8_0070, "VDP2_SCXIN0"),
+    (0x25F8_0072, "VDP2_SCXDN0"),
+    (0x25F8_0074, "VDP2_SCYIN0"),
+    (0x25F8_0076, "VDP2_SCYDN0"),
+    (0x25F8_0078, "VDP2_ZMXIN0"),
+    (0x25F8_007A, "VDP2_ZMXDN0"),
+    (0x25F8_007C, "VDP2_ZMYIN0"),
+    (0x25F8_007E, "VDP2_ZMYDN0"),
+    (0x25F8_0080, "VDP2_SCXIN1"),
+    (0x25F8_0082, "VDP2_SCXDN1"),
+    (0x25F8_0084, "VDP2_SCYIN1"),
+    (0x25F8_0086, "VDP2_SCYDN1"),
+    (0x25F8_0088, "VDP2_ZMXIN1"),
+    (0x25F8_008A, "VDP2_ZMXDN1"),
+    (0x25F8_008C, "VDP2_ZMYIN1"),
+    (0x25F8_008E, "VDP2_ZMYDN1"),
+    (0x25F8_0090, "VDP2_SCXN2"),
+    (0x25F8_0092, "VDP2_SCYN2"),
+    (0x25F8_0094, "VDP2_SCXN3"),
+    (0x25F8_0096, "VDP2_SCYN3"),
+    (0x25F8_0098, "VDP2_ZMCTL"),
+    (0x25F8_009A, "VDP2_SCRCTL"),
+    (0x25F8_009C, "VDP2_VCSTAU"),
+    (0x25F8_009E, "VDP2_VCSTAL"),
+    (0x25F8_00A0, "VDP2_LSTA0U"),
+    (0x25F8_00A2, "VDP2_LSTA0L"),
+    (0x25F8_00A4, "VDP2_LSTA1U"),
+    (0x25F8_00A6, "VDP2_LSTA1L"),
+    (0x25F8_00A8, "VDP2_LCTAU"),
+    (0x25F8_00AA, "VDP2_LCTAL"),
+    (0x25F8_00AC, "VDP2_BKTAU"),
+    (0x25F8_00AE, "VDP2_BKTAL"),
+    (0x25F8_00B0, "VDP2_RPMD"),
+    (0x25F8_00B2, "VDP2_RPRCTL"),
+    (0x25F8_00B4, "VDP2_KTCTL"),
+    (0x25F8_00B6, "VDP2_KTAOF"),
+    (0x25F8_00B8, "VDP2_OVPNRA"),
+    (0x25F8_00BA, "VDP2_OVPNRB"),
+    (0x25F8_00BC, "VDP2_RPTAU"),
+    (0x25F8_00BE, "VDP2_RPTAL"),
+    (0x25F8_00C0, "VDP2_WPSX0"),
+    (0x25F8_00C2, "VDP2_WPSY0"),
+    (0x25F8_00C4, "VDP2_WPEX0"),
+    (0x25F8_00C6, "VDP2_WPEY0"),
+    (0x25F8_00C8, "VDP2_WPSX1"),
+    (0x25F8_00CA, "VDP2_WPSY1"),
+    (0x25F8_00CC, "VDP2_WPEX1"),
+    (0x25F8_00CE, "VDP2_WPEY1"),
+    (0x25F8_00D0, "VDP2_WCTLA"),
+    (0x25F8_00D2, "VDP2_WCTLB"),
+    (0x25F8_00D4, "VDP2_WCTLC"),
+    (0x25F8_00D6, "VDP2_WCTLD"),
+    (0x25F8_00D8, "VDP2_LWTA0U"),
+    (0x25F8_00DA, "VDP2_LWTA0L"),
+    (0x25F8_00DC, "VDP2_LWTA1U"),
+    (0x25F8_00DE, "VDP2_LWTA1L"),
+    (0x25F8_00E0, "VDP2_SPCTL"),
+    (0x25F8_00E2, "VDP2_SDCTL"),
+    (0x25F8_00E4, "VDP2_CRAOFA"),
+    (0x25F8_00E6, "VDP2_CRAOFB"),
+    (0x25F8_00E8, "VDP2_LNCLEN"),
+    (0x25F8_00EA, "VDP2_SFPRMD"),
+    (0x25F8_00EC, "VDP2_CCCTL"),
+    (0x25F8_00EE, "VDP2_SFCCMD"),
+    (0x25F8_00F0, "VDP2_PRISA"),
+    (0x25F8_00F2, "VDP2_PRISB"),
+    (0x25F8_00F4, "VDP2_PRISC"),
+    (0x25F8_00F6, "VDP2_PRISD"),
+    (0x25F8_00F8, "VDP2_PRINA"),
+    (0x25F8_00FA, "VDP2_PRINB"),
+    (0x25F8_00FC, "VDP2_PRIR"),
+    (0x25F8_0100, "VDP2_CCRSA"),
+    (0x25F8_0102, "VDP2_CCRSB"),
+    (0x25F8_0104, "VDP2_CCRSC"),
+    (0x25F8_0106, "VDP2_CCRSD"),
+    (0x25F8_0108, "VDP2_CCRNA"),
+    (0x25F8_010A, "VDP2_CCRNB"),
+    (0x25F8_010C, "VDP2_CCRR"),
+    (0x25F8_010E, "VDP2_CCRLB"),
+    (0x25F8_0110, "VDP2_CLOFEN"),
+    (0x25F8_0112, "VDP2_CLOFSL"),
+    (0x25F8_0114, "VDP2_COAR"),
+    (0x25F8_0116, "VDP2_COAG"),
+    (0x25F8_0118, "VDP2_COAB"),
+    (0x25F8_011A, "VDP2_COBR"),
+    (0x25F8_011C, "VDP2_COBG"),
+    (0x25F8_011E, "VDP2_COBB"),
+    // SCU
+    (0x25FE_0000, "SCU_D0R"),
+    (0x25FE_0004, "SCU_D0W"),
+    (0x25FE_0008, "SCU_D0C"),
+    (0x25FE_000C, "SCU_D0AD"),
+    (0x25FE_0010, "SCU_D0EN"),
+    (0x25FE_0014, "SCU_D0MD"),
+    (0x25FE_0020, "SCU_D1R"),
+    (0x25FE_0024, "SCU_D1W"),
+    (0x25FE_0028, "SCU_D1C"),
+    (0x25FE_002C, "SCU_D1AD"),
+    (0x25FE_0030, "SCU_D1EN"),
+    (0x25FE_0034, "SCU_D1MD"),
+    (0x25FE_0040, "SCU_D2R"),
+    (0x25FE_0044, "SCU_D2W"),
+    (0x25FE_0048, "SCU_D2C"),
+    (0x25FE_004C, "SCU_D2AD"),
+    (0x25FE_0050, "SCU_D2EN"),
+    (0x25FE_0054, "SCU_D2MD"),
+    (0x25FE_0060, "SCU_DSTP"),
+    (0x25FE_007C, "SCU_DSTA"),
+    (0x25FE_0080, "SCU_PPAF"),
+    (0x25FE_0084, "SCU_PPD"),
+    (0x25FE_0088, "SCU_PDA"),
+    (0x25FE_008C, "SCU_PDD"),
+    (0x25FE_0090, "SCU_T0C"),
+    (0x25FE_0094, "SCU_T1S"),
+    (0x25FE_0098, "SCU_T1MD"),
+    (0x25FE_00A0, "SCU_IMS"),
+    (0x25FE_00A4, "SCU_IST"),
+    (0x25FE_00A8, "SCU_AIACK"),
+    (0x25FE_00B0, "SCU_ASR0"),
+    (0x25FE_00B4, "SCU_ASR1"),
+    (0x25FE_00B8, "SCU_AREF"),
+    (0x25FE_00C4, "SCU_RSEL"),
+    (0x25FE_00C8, "SCU_VER"),
+    // SH-2 serial communication interface
+    (0xFFFF_FE00, "SCI_SMR"),
+    (0xFFFF_FE01, "SCI_BRR"),
+    (0xFFFF_FE02, "SCI_SCR"),
+    (0xFFFF_FE03, "SCI_TDR"),
+    (0xFFFF_FE04, "SCI_SSR"),
+    (0xFFFF_FE05, "SCI_RDR"),
+    // SH-2 free-running timer
+    (0xFFFF_FE10, "FRT_TIER"),
+    (0xFFFF_FE11, "FRT_FTCSR"),
+    (0xFFFF_FE12, "FRT_FRCH"),
+    (0xFFFF_FE13, "FRT_FRCL"),
+    (0xFFFF_FE14, "FRT_OCRH"),
+    (0xFFFF_FE15, "FRT_OCRL"),
+    (0xFFFF_FE16, "FRT_TCR"),
+    (0xFFFF_FE17, "FRT_TOCR"),
+    (0xFFFF_FE18, "FRT_FICRH"),
+    (0xFFFF_FE19, "FRT_FICRL"),
+    // SH-2 interrupt controller, DMA request/response selection
+    (0xFFFF_FE60, "INTC_IPRB"),
+    (0xFFFF_FE62, "INTC_VCRA"),
+    (0xFFFF_FE64, "INTC_VCRB"),
+    (0xFFFF_FE66, "INTC_VCRC"),
+    (0xFFFF_FE68, "INTC_VCRD"),
+    (0xFFFF_FE71, "DMAC_DRCR0"),
+    (0xFFFF_FE72, "DMAC_DRCR1"),
+    // SH-2 watchdog timer, power-down and cache
+    (0xFFFF_FE80, "WDT_WTCSR"),
+    (0xFFFF_FE81, "WDT_WTCNT"),
+    (0xFFFF_FE82, "WDT_RSTCSR"),
+    (0xFFFF_FE91, "SBYCR"),
+    (0xFFFF_FE92, "CCR"),
+    (0xFFFF_FEE0, "INTC_ICR"),
+    (0xFFFF_FEE2, "INTC_IPRA"),
+    (0xFFFF_FEE4, "INTC_VCRWDT"),
+    // SH-2 division unit
+    (0xFFFF_FF00, "DIVU_DVSR"),
+    (0xFFFF_FF04, "DIVU_DVDNT"),
+    (0xFFFF_FF08, "DIVU_DVCR"),
+    (0xFFFF_FF0C, "DIVU_VCRDIV"),
+    (0xFFFF_FF10, "DIVU_DVDNTH"),
+    (0xFFFF_FF14, "DIVU_DVDNTL"),
+    // SH-2 user break controller
+    (0xFFFF_FF40, "UBC_BARAH"),
+    (0xFFFF_FF42, "UBC_BARAL"),
+    (0xFFFF_FF44, "UBC_BAMRAH"),
+    (0xFFFF_FF46, "UBC_BAMRAL"),
+    (0xFFFF_FF48, "UBC_BBRA"),
+    (0xFFFF_FF60, "UBC_BARBH"),
+    (0xFFFF_FF62, "UBC_BARBL"),
+    (0xFFFF_FF64, "UBC_BAMRBH"),
+    (0xFFFF_FF66, "UBC_BAMRBL"),
+    (0xFFFF_FF68, "UBC_BBRB"),
+    (0xFFFF_FF70, "UBC_BDRBH"),
+    (0xFFFF_FF72, "UBC_BDRBL"),
+    (0xFFFF_FF74, "UBC_BDMRBH"),
+    (0xFFFF_FF76, "UBC_BDMRBL"),
+    (0xFFFF_FF78, "UBC_BRCR"),
+    // SH-2 DMA controller
+    (0xFFFF_FF80, "DMAC_SAR0"),
+    (0xFFFF_FF84, "DMAC_DAR0"),
+    (0xFFFF_FF88, "DMAC_TCR0"),
+    (0xFFFF_FF8C, "DMAC_CHCR0"),
+    (0xFFFF_FF90, "DMAC_SAR1"),
+    (0xFFFF_FF94, "DMAC_DAR1"),
+    (0xFFFF_FF98, "DMAC_TCR1"),
+    (0xFFFF_FF9C, "DMAC_CHCR1"),
+    (0xFFFF_FFA0, "DMAC_VCRDMA0"),
+    (0xFFFF_FFA8, "DMAC_VCRDMA1"),
+    (0xFFFF_FFB0, "DMAC_DMAOR"),
+    // SH-2 bus state controller
+    (0xFFFF_FFE0, "BSC_BCR1"),
+    (0xFFFF_FFE4, "BSC_BCR2"),
+    (0xFFFF_FFE8, "BSC_WCR"),
+    (0xFFFF_FFEC, "BSC_MCR"),
+    (0xFFFF_FFF0, "BSC_RTCSR"),
+    (0xFFFF_FFF4, "BSC_RTCNT"),
+    (0xFFFF_FFF8, "BSC_RTCOR"),
+];
+
+// BIOS routines are called through pointers the BIOS keeps in work RAM,
+// e.g. `(**(void (**)(Uint32, Uint32))0x06000340)(and, or)` for SYS_CHGSCUIM.
+const BIOS_CALLS: &[(u32, &str)] = &[
+    (0x0600_026C, "SYS_EXECDMP"),
+    (0x0600_0274, "SYS_CHKMPEG"),
+    (0x0600_0280, "SYS_CHGUIPR"),
+    (0x0600_0300, "SYS_SETUINT"),
+    (0x0600_0304, "SYS_GETUINT"),
+    (0x0600_0310, "SYS_SETSINT"),
+    (0x0600_0314, "SYS_GETSINT"),
+    (0x0600_0320, "SYS_CHGSYSCK"),
+    (0x0600_0330, "SYS_TASSEM"),
+    (0x0600_0334, "SYS_CLRSEM"),
+    (0x0600_0340, "SYS_CHGSCUIM"),
+    (0x0600_0344, "SYS_SETSCUIM"),
+];
+
+// The BIOS routine whose pointer is stored at addr.
+pub fn bios_call_name(addr: u32) -> Option<&'static str> {
+    BIOS_CALLS
+        .binary_search_by_key(&addr, |&(vector, _)| vector)
+        .ok()
+        .map(|index| BIOS_CALLS[index].1)
+}
+
+pub fn register_name(addr: u32) -> Option<&'static str> {
+    REGISTERS
+        .binary_search_by_key(&addr, |&(register, _)| register)
+        .ok()
+        .map(|index| REGISTERS[index].1)
+}
+
+// The SH-2 sees the same memory through the cached and cache-through areas,
+// selected by the top three address bits. Returns the cached address and the
+// area's offset from it. The associative purge and address array areas are
+// left alone: code rarely reaches data through them, and values there are
+// more often float or fixed-point constants such as 0x46010000.
+pub fn cache_alias(addr: u32) -> (u32, u32) {
+    match addr >> 29 {
+        0..=1 => (addr & 0x1FFF_FFFF, addr & 0xE000_0000),
+        _ => (addr, 0),
+    }
+}
+
+// addr followed by the address that aliases it in the other cache area.
+pub fn aliases(addr: u32) -> Vec<u32> {
+    let mut aliases = vec![addr];
+    if addr >> 29 <= 1 {
+        aliases.push(addr ^ 0x2000_0000);
+    }
+    aliases
+}
+
+// saturn_regs.h
+pub fn header() -> String {
+    let mut header = String::from("#ifndef SATURN_REGS_H\n#define SATURN_REGS_H\n\n");
+    for (addr, name) in REGISTERS {
+        header.push_str(&format!("#define {} 0x{:08X}\n", name, addr));
+    }
+    header.push_str("\n// BIOS routine pointers\n");
+    for (addr, name) in BIOS_CALLS {
+        header.push_str(&format!("#define {} 0x{:08X}\n", name, addr));
+    }
+    header.push_str("\n#endif\n");
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_map_is_sorted_and_unique() {
+        assert!(REGISTERS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(BIOS_CALLS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let mut names: Vec<_> = REGISTERS.iter().map(|(_, name)| name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), REGISTERS.len());
+
+        assert_eq!(register_name(0xFFFF_FE10), Some("FRT_TIER"));
+        assert_eq!(register_name(0x25FE_00A0), Some("SCU_IMS"));
+        assert_eq!(register_name(0x0600_0000), None);
+        assert!(header().contains("#define VDP2_TVMD 0x25F80000\n"));
+    }
+}
@@ -0,0 +1,286 @@
+// Library function signatures: masked byte patterns for functions statically
+// linked from SGL, SBL or libc. One signature per line:
+//
+//     <library> <name> <pattern>
+//
+// The pattern is hex, with `.` for every nibble that changes with the link
+// address, such as literal pool values and branch displacements. Spaces in
+// the pattern are ignored and `#` starts a comment.
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub library: String,
+    pub name: String,
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+// A field of an object file's function that the linker fills in, at the
+// offset of its instruction or word.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fixup {
+    Word32,
+    Disp12,
+    Disp8,
+}
+
+impl Signature {
+    // mask has the bits of bytes that must match
+    pub fn new(library: &str, name: &str, bytes: Vec<u8>, mask: Vec<u8>) -> Signature {
+        let bytes = bytes
+            .iter()
+            .zip(&mask)
+            .map(|(byte, mask)| byte & mask)
+            .collect();
+        Signature {
+            library: library.to_string(),
+            name: name.to_string(),
+            bytes,
+            mask,
+        }
+    }
+
+    // The signature of an unlinked function. Relocated fields, bsr
+    // displacements and the literals loaded PC-relative are masked.
+    pub fn from_function(
+        library: &str,
+        name: &str,
+        bytes: &[u8],
+        fixups: &[(usize, Fixup)],
+    ) -> Signature {
+        let mut mask = vec![0xff; bytes.len()];
+        let clear = |mask: &mut Vec<u8>, start: usize, size: usize| {
+            for byte in mask.iter_mut().skip(start).take(size) {
+                *byte = 0;
+            }
+        };
+        for offset in (0..bytes.len() & !1).step_by(2) {
+            let op = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+            let disp = (op & 0xff) as usize;
+            match op >> 12 {
+                // mov.w @(disp,pc),rn
+                0x9 => clear(&mut mask, offset + 4 + disp * 2, 2),
+                // mov.l @(disp,pc),rn
+                0xd => clear(&mut mask, ((offset + 4) & !3) + disp * 4, 4),
+                // bsr
+                0xb => {
+                    clear(&mut mask, offset + 1, 1);
+                    mask[offset] = 0xf0;
+                }
+                _ => {}
+            }
+        }
+        for &(offset, fixup) in fixups {
+            match fixup {
+                Fixup::Word32 => clear(&mut mask, offset, 4),
+                Fixup::Disp8 => clear(&mut mask, offset + 1, 1),
+                Fixup::Disp12 => {
+                    clear(&mut mask, offset + 1, 1);
+                    if let Some(byte) = mask.get_mut(offset) {
+                        *byte &= 0xf0;
+                    }
+                }
+            }
+        }
+        Signature::new(library, name, bytes.to_vec(), mask)
+    }
+
+    // The pattern as written in a signature file, a word per group.
+    pub fn pattern(&self) -> String {
+        let nibbles: Vec<String> = self
+            .bytes
+            .iter()
+            .zip(&self.mask)
+            .map(|(byte, mask)| {
+                [(byte >> 4, mask >> 4), (byte & 0xf, mask & 0xf)]
+                    .iter()
+                    .map(|&(nibble, mask)| {
+                        if mask == 0 {
+                            '.'
+                        } else {
+                            char::from_digit(nibble.into(), 16).unwrap()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        nibbles
+            .chunks(2)
+            .map(|word| word.concat())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.bytes.len()
+            && self
+                .bytes
+                .iter()
+                .zip(&self.mask)
+                .zip(data)
+                .all(|((byte, mask), data)| data & mask == *byte)
+    }
+
+    fn parse_line(line: &str) -> Result<Option<Signature>, String> {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(library), Some(name)) = (fields.next(), fields.next()) else {
+            return Err("expected <library> <name> <pattern>".to_string());
+        };
+        let nibbles: Vec<char> = fields.flat_map(str::chars).collect();
+        if nibbles.is_empty() || !nibbles.len().is_multiple_of(2) {
+            return Err(format!("{} has an odd or empty pattern", name));
+        }
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+        for pair in nibbles.chunks(2) {
+            let (mut byte, mut byte_mask) = (0, 0);
+            for &nibble in pair {
+                byte <<= 4;
+                byte_mask <<= 4;
+                if nibble != '.' {
+                    let value = nibble.to_digit(16).ok_or_else(|| {
+                        format!("{} has an invalid pattern digit {:?}", name, nibble)
+                    })?;
+                    byte |= value as u8;
+                    byte_mask |= 0xf;
+                }
+            }
+            bytes.push(byte);
+            mask.push(byte_mask);
+        }
+        Ok(Some(Signature::new(library, name, bytes, mask)))
+    }
+}
+
+// Problems are (line, message).
+pub fn parse(contents: &str) -> Result<Vec<Signature>, (usize, String)> {
+    let mut signatures = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        match Signature::parse_line(line) {
+            Ok(Some(signature)) => signatures.push(signature),
+            Ok(None) => {}
+            Err(message) => return Err((index + 1, message)),
+        }
+    }
+    Ok(signatures)
+}
+
+pub fn format_file(signatures: &[Signature]) -> String {
+    signatures
+        .iter()
+        .map(|signature| {
+            format!(
+                "{} {} {}\n",
+                signature.library,
+                signature.name,
+                signature.pattern()
+            )
+        })
+        .collect()
+}
+
+// Match signatures against the functions starting at starts, ending at end at
+// the latest. The longest signature wins where several match.
+pub fn scan<'a>(
+    signatures: &'a [Signature],
+    data: &[u8],
+    starts: &[usize],
+    end: usize,
+) -> BTreeMap<usize, &'a Signature> {
+    // most signatures start with a fixed instruction, index them by it
+    let mut by_first_word = HashMap::<[u8; 2], Vec<&Signature>>::new();
+    let mut unindexed = Vec::new();
+    for signature in signatures.iter().filter(|signature| signature.size() >= 2) {
+        if signature.mask[..2] == [0xff, 0xff] {
+            by_first_word
+                .entry([signature.bytes[0], signature.bytes[1]])
+                .or_default()
+                .push(signature);
+        } else {
+            unindexed.push(signature);
+        }
+    }
+
+    let end = end.min(data.len());
+    let mut matches = BTreeMap::new();
+    for &offset in starts.iter().filter(|&&offset| offset + 2 <= end) {
+        let first_word = [data[offset], data[offset + 1]];
+        let best = by_first_word
+            .get(&first_word)
+            .into_iter()
+            .flatten()
+            .chain(&unindexed)
+            .filter(|signature| {
+                offset + signature.size() <= end && signature.matches(&data[offset..])
+            })
+            .max_by_key(|signature| signature.size());
+        if let Some(signature) = best {
+            matches.insert(offset, *signature);
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masked_patterns_round_trip_and_match() {
+        let signatures = parse(
+            "# cygnus libc\n\
+             libc strlen 6143 d1.. 410b 0009 # the literal load is masked\n\
+             libc strlen_head 6143 d1..\n",
+        )
+        .unwrap();
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(
+            signatures[0].mask,
+            [0xff, 0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0xff]
+        );
+
+        let data = [
+            0x00, 0x09, 0x61, 0x43, 0xd1, 0x07, 0x41, 0x0b, 0x00, 0x09, 0x61, 0x43, 0xd1, 0x07,
+        ];
+        let matches = scan(&signatures, &data, &[2], data.len());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[&2].name, "strlen");
+        // the same bytes inside a function are not a match
+        assert!(scan(&signatures, &data, &[0], data.len()).is_empty());
+
+        assert_eq!(
+            parse("libc memcpy 2fe6 d1.").unwrap_err(),
+            (1, "memcpy has an odd or empty pattern".to_string())
+        );
+    }
+
+    #[test]
+    fn test_object_functions_mask_literals_calls_and_relocations() {
+        let bytes = [
+            0x4f, 0x22, // sts.l pr,@-r15
+            0xd1, 0x02, // mov.l @(8,pc),r1
+            0xb0, 0x10, // bsr
+            0x00, 0x09, // nop
+            0xa0, 0x02, // bra, relocated
+            0x00, 0x09, // nop
+            0x06, 0x00, 0x40, 0x00, // literal
+        ];
+        let signature = Signature::from_function("sgl", "slInit", &bytes, &[(8, Fixup::Disp12)]);
+        assert_eq!(
+            signature.pattern(),
+            "4f22 d102 b... 0009 a... 0009 .... ...."
+        );
+        let file = format_file(std::slice::from_ref(&signature));
+        assert_eq!(parse(&file).unwrap(), [signature]);
+    }
+}
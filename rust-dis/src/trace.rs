@@ -0,0 +1,179 @@
+// Execution traces saved from an emulator: every PC the SH-2 executed, in
+// order. A text trace has a hex address at the start of each line, as most
+// emulator trace loggers and debugger dumps write them; anything after it and
+// `#` comments are ignored. A binary trace is a list of 32-bit addresses.
+
+use crate::saturn;
+use std::collections::BTreeSet;
+
+// Problems are (line, message).
+pub fn parse_text(contents: &[u8]) -> Result<Vec<u32>, (usize, String)> {
+    let text = std::str::from_utf8(contents).map_err(|error| {
+        let line = contents[..error.valid_up_to()]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count();
+        (line + 1, "is not text, is the trace binary?".to_string())
+    })?;
+    let mut addresses = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(field) = line.split_whitespace().next() else {
+            continue;
+        };
+        let hex = field.trim_end_matches(':');
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        let address = u32::from_str_radix(hex, 16)
+            .map_err(|_| (index + 1, format!("{} is not a hex address", field)))?;
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+// Problems are (byte offset, message).
+pub fn parse_binary(contents: &[u8], little_endian: bool) -> Result<Vec<u32>, (usize, String)> {
+    if !contents.len().is_multiple_of(4) {
+        return Err((
+            contents.len(),
+            "not a whole number of addresses".to_string(),
+        ));
+    }
+    Ok(contents
+        .chunks_exact(4)
+        .map(|word| {
+            let word = word.try_into().unwrap();
+            if little_endian {
+                u32::from_le_bytes(word)
+            } else {
+                u32::from_be_bytes(word)
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Coverage {
+    // every instruction executed
+    pub executed: BTreeSet<u32>,
+    // the targets of executed calls
+    pub entries: BTreeSet<u32>,
+    // literal pool words read by executed instructions
+    pub literals: BTreeSet<u32>,
+}
+
+// What a trace shows about the code. Addresses are moved out of the
+// cache-through area, and instruction_at gives the opcode at
+// an address when it is in the target.
+pub fn coverage(trace: &[u32], instruction_at: impl Fn(u32) -> Option<u16>) -> Coverage {
+    let trace: Vec<u32> = trace
+        .iter()
+        .map(|&address| saturn::cache_alias(address).0)
+        .collect();
+    let mut coverage = Coverage::default();
+    for (index, &pc) in trace.iter().enumerate() {
+        coverage.executed.insert(pc);
+        let Some(op) = instruction_at(pc) else {
+            continue;
+        };
+        let disp = (op & 0xff) as u32;
+        match op >> 12 {
+            // mov.w @(disp,pc),rn
+            0x9 => {
+                coverage.literals.insert(pc + 4 + disp * 2);
+            }
+            // mov.l @(disp,pc),rn
+            0xd => {
+                let target = ((pc + 4) & !3) + disp * 4;
+                coverage.literals.extend([target, target + 2]);
+            }
+            _ => {}
+        }
+        // bsr, jsr and bsrf land two steps on, after the delay slot
+        let is_call = op >> 12 == 0xb || op & 0xf0ff == 0x400b || op & 0xf0ff == 0x0003;
+        if is_call {
+            if let Some(&entry) = trace.get(index + 2) {
+                coverage.entries.insert(entry);
+            }
+        }
+    }
+    coverage
+}
+
+impl Coverage {
+    // Ranges of start..end, as [start, end), whose instructions neither ran
+    // nor were read as literals.
+    pub fn untouched(&self, start: u32, end: u32) -> Vec<(u32, u32)> {
+        self.runs(start, end, |address| {
+            !self.executed.contains(&address) && !self.literals.contains(&address)
+        })
+    }
+
+    // Ranges of start..end whose instructions ran.
+    pub fn ran(&self, start: u32, end: u32) -> Vec<(u32, u32)> {
+        self.runs(start, end, |address| self.executed.contains(&address))
+    }
+
+    fn runs(&self, start: u32, end: u32, wanted: impl Fn(u32) -> bool) -> Vec<(u32, u32)> {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for address in (start..end).step_by(2).filter(|&address| wanted(address)) {
+            match ranges.last_mut() {
+                Some(range) if range.1 == address => range.1 = address + 2,
+                _ => ranges.push((address, address + 2)),
+            }
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_give_entries_literals_and_untouched_code() {
+        let text =
+            "# pc trace\n06000000: sts.l pr,@-r15\n0x06000002\n06000004\n06000006 nop\n26000010\n";
+        let trace = parse_text(text.as_bytes()).unwrap();
+        let binary: Vec<u8> = trace.iter().flat_map(|pc| pc.to_le_bytes()).collect();
+        assert_eq!(parse_binary(&binary, true).unwrap(), trace);
+        assert_eq!(
+            parse_binary(&binary[1..], true),
+            Err((19, "not a whole number of addresses".to_string()))
+        );
+        assert_eq!(
+            parse_text(b"06000000\nzz\n"),
+            Err((2, "zz is not a hex address".to_string()))
+        );
+        // big-endian PCs without a zero byte are still valid UTF-8
+        assert_eq!(parse_text(&[0x06, 0x04, 0x12, 0x34]).unwrap_err().0, 1);
+        assert_eq!(
+            parse_text(b"06000000\n\xff"),
+            Err((2, "is not text, is the trace binary?".to_string()))
+        );
+
+        let code = [
+            0x4f22, // sts.l pr,@-r15
+            0xd102, // mov.l @(8,pc),r1
+            0xb004, // bsr 0x06000010
+            0x0009, // nop
+            0x0000, 0x0000, // unused
+            0x0600, 0x0000, // literal
+            0x000b, // 0x06000010: rts
+        ];
+        let instruction_at = |pc: u32| {
+            code.get((pc.checked_sub(0x06000000)? / 2) as usize)
+                .copied()
+        };
+        let coverage = coverage(&trace, instruction_at);
+        assert_eq!(coverage.entries, BTreeSet::from([0x06000010]));
+        assert_eq!(coverage.literals, BTreeSet::from([0x0600000C, 0x0600000E]));
+        assert_eq!(
+            coverage.untouched(0x06000000, 0x06000012),
+            [(0x06000008, 0x0600000C)]
+        );
+        assert_eq!(
+            coverage.ran(0x06000000, 0x06000012),
+            [(0x06000000, 0x06000008), (0x06000010, 0x06000012)]
+        );
+    }
+}
@@ -26,9 +26,8 @@ sh-elf-ld --no-check-sections -nostdlib \
 sh-elf-objcopy -O binary "$build_dir/original.elf" \
     "$build_dir/original.bin"
 
-echo "b5bd17ac8e0e1ce45801ed22c984f73cb6ad2c12  $build_dir/original.bin" | sha1sum -c -
-
-target/release/rust-dis "$fixture_dir/config.yaml"
+# rust-dis refuses to split if original.bin does not match the config's sha1
+target/release/rust-dis split "$fixture_dir/config.yaml"
 
 sh-elf-ld --no-check-sections -nostdlib \
     -T "$build_dir/fixture.ld" \
@@ -36,5 +35,6 @@ sh-elf-ld --no-check-sections -nostdlib \
 sh-elf-objcopy -O binary "$build_dir/actual.elf" \
     "$build_dir/actual.bin"
 
-cmp "$build_dir/original.bin" "$build_dir/actual.bin"
+target/release/rust-dis verify "$fixture_dir/config.yaml" \
+    --build "$build_dir/actual.bin"
 echo "SH-2 named data fixture: PASS"